@@ -1,33 +1,98 @@
-import init, { draw, spawn_firework, resize_canvas } from './pkg/new_years.js';
+import init, { FireworkShow } from './pkg/new_years.js';
 
-let firework_counter = 0;
-let firework_spawner_handle = 0;
+/* Run the show in a worker, so it doesn't compete with the page. There is no
+ * sound in a worker. */
+function runInWorker(canvas, params) {
+    const offscreen = canvas.transferControlToOffscreen();
+    const worker = new Worker('./worker.js', { type: 'module' });
 
-/* Limit the amount of fireworks based on window width. */
-let max_fireworks = window.innerWidth / 100;
+    worker.postMessage({
+        type: 'start',
+        canvas: offscreen,
+        width: canvas.clientWidth,
+        height: canvas.clientHeight,
+        pixel_ratio: window.devicePixelRatio,
+        query: window.location.search,
+    }, [offscreen]);
 
-async function run() {
-    await init();
-
-    setInterval(draw, 10);
+    /* If the show fails to start in the worker, run it on the page. The
+     * canvas was handed to the worker, so it is replaced by a fresh one. */
+    worker.onmessage = (event) => {
+        if (event.data.type === 'error') {
+            console.error(`Running the show on the page instead of in a worker: ${event.data.message}`);
+            worker.terminate();
+            canvas.replaceWith(canvas.cloneNode(false));
+            runOnPage(params);
+        }
+    };
 
-    firework_spawner_handle = setInterval(() => {
-        spawn_firework();
-        firework_counter += 1;
+    window.addEventListener('resize', () => {
+        worker.postMessage({
+            type: 'resize',
+            width: canvas.clientWidth,
+            height: canvas.clientHeight,
+            pixel_ratio: window.devicePixelRatio,
+        });
+    });
 
-        /* Spawn until the max firework amount is reached. */
+    document.addEventListener('visibilitychange', () => {
+        worker.postMessage({ type: document.hidden ? 'pause' : 'resume' });
+    });
 
-        if (firework_counter >= max_fireworks) {
-            clearInterval(firework_spawner_handle);
-        }
-        /* Spawn fireworks in regular intervals such that the maximum is reached
-         * in 5 seconds. */
-    }, 5000 / max_fireworks);
+    /* With ?launch_on_click a click launches a rocket from where the page
+     * was clicked, otherwise it spawns a firework. */
+    if (params.has('launch_on_click')) {
+        window.onclick = (event) => worker.postMessage({ type: 'click', x: event.clientX });
+    } else {
+        window.onclick = () => worker.postMessage({ type: 'spawn' });
+    }
 
-    /* Change the canvas resolution when the window is resized. */
-    window.onresize = (event) => {
-        resize_canvas()
+    window.onpointermove = (event) => {
+        worker.postMessage({ type: 'pointer', x: event.clientX, y: event.clientY });
     };
+    document.documentElement.onpointerleave = () => worker.postMessage({ type: 'pointer_leave' });
+}
+
+/* Run the show on the page, with sound. */
+async function runOnPage(params) {
+    await init();
+
+    /* Show the fireworks on the entire screen. If this fails, the reason is
+     * shown on the page. */
+    const show = FireworkShow.fill_window('fireworks');
+    show.configure(window.location.search);
+    show.run();
+
+    /* Toggle the sound on click, browsers only allow audio after user
+     * interaction. With ?launch_on_click a click launches a rocket from where
+     * the page was clicked instead, and the m key toggles the sound. */
+    if (params.has('launch_on_click')) {
+        window.onclick = (event) => show.click(event.clientX);
+        window.onkeydown = (event) => {
+            if (event.key === 'm') {
+                show.set_muted(!show.is_muted());
+            }
+        };
+    } else {
+        window.onclick = (event) => {
+            show.set_muted(!show.is_muted());
+        };
+    }
+
+    /* Fields following the pointer, like with ?pointer=vortex, push the
+     * sparks around. */
+    window.onpointermove = (event) => show.move_pointer(event.clientX, event.clientY);
+    document.documentElement.onpointerleave = () => show.leave_pointer();
+}
+
+async function run() {
+    const canvas = document.getElementById('fireworks');
+    const params = new URLSearchParams(window.location.search);
+    if (params.has('worker') && canvas && 'transferControlToOffscreen' in canvas) {
+        runInWorker(canvas, params);
+    } else {
+        await runOnPage(params);
+    }
 }
 
-run();
\ No newline at end of file
+run();
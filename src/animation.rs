@@ -0,0 +1,268 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{DedicatedWorkerGlobalScope, Document, EventTarget, Window};
+
+use crate::error::Error;
+use crate::graphics::STEPS_PER_SECOND;
+use crate::show::Stage;
+
+/* The time in milliseconds in which the amount of fireworks ramps up to the
+ * maximum. */
+const RAMP_DURATION: f64 = 5000.;
+
+/* The most steps simulated for a single frame. After a long frame the show
+ * skips ahead, instead of freezing to catch up. */
+const MAX_STEPS_PER_FRAME: f64 = 10.;
+
+/* The timing of the animation loop. */
+#[derive(Default)]
+struct Clock {
+    /* The pending animation frame request. */
+    request: Option<i32>,
+    paused: bool,
+    /* The timestamp of the last frame, forgotten when the loop is
+     * interrupted so the time in between isn't simulated. */
+    last_time: Option<f64>,
+    /* The time in milliseconds which hasn't been simulated yet. */
+    lag: f64,
+    since_spawn: f64,
+}
+
+type FrameCallback = Rc<RefCell<Option<Closure<dyn FnMut(f64)>>>>;
+
+/* An event listener, removed again when the animation stops. */
+type Listener = (EventTarget, &'static str, Closure<dyn FnMut()>);
+
+/* The global scope an animation runs in: the window of a page, or a worker
+ * drawing on an OffscreenCanvas. */
+#[derive(Clone)]
+enum Scope {
+    Window(Window),
+    Worker(DedicatedWorkerGlobalScope),
+}
+
+impl Scope {
+    fn current() -> Result<Self, Error> {
+        let global = js_sys::global();
+
+        if let Some(window) = global.dyn_ref::<Window>() {
+            Ok(Scope::Window(window.clone()))
+        } else if let Some(worker) = global.dyn_ref::<DedicatedWorkerGlobalScope>() {
+            Ok(Scope::Worker(worker.clone()))
+        } else {
+            Err(Error::NoWindow)
+        }
+    }
+
+    fn request_animation_frame(&self, callback: &js_sys::Function) -> Result<i32, JsValue> {
+        match self {
+            Scope::Window(window) => window.request_animation_frame(callback),
+            Scope::Worker(worker) => worker.request_animation_frame(callback),
+        }
+    }
+
+    fn cancel_animation_frame(&self, request: i32) -> Result<(), JsValue> {
+        match self {
+            Scope::Window(window) => window.cancel_animation_frame(request),
+            Scope::Worker(worker) => worker.cancel_animation_frame(request),
+        }
+    }
+}
+
+/* An animation loop which simulates and draws a show every animation frame,
+ * spawns fireworks until the canvas is full, follows the size of the canvas
+ * and pauses while the page is hidden. In a worker the page takes care of
+ * the size, and of pausing. It stops when it is dropped. */
+pub struct Animation {
+    scope: Scope,
+    document: Option<Document>,
+    clock: Rc<RefCell<Clock>>,
+    frame: FrameCallback,
+    listeners: Vec<Listener>,
+}
+
+impl Animation {
+    /* Start animating the given stage. */
+    pub fn start(stage: Rc<RefCell<Stage>>) -> Result<Self, Error> {
+        let scope = Scope::current()?;
+        let clock = Rc::new(RefCell::new(Clock::default()));
+        let frame: FrameCallback = Rc::new(RefCell::new(None));
+
+        *frame.borrow_mut() = Some(Closure::new({
+            let (scope, stage, clock, frame) =
+                (scope.clone(), stage.clone(), clock.clone(), frame.clone());
+
+            move |time: f64| {
+                let mut clock = clock.borrow_mut();
+                clock.request = None;
+
+                let elapsed = clock.last_time.map_or(0., |last| (time - last).max(0.));
+                clock.last_time = Some(time);
+
+                advance(&mut clock, &mut stage.borrow_mut(), elapsed);
+
+                clock.request = request_frame(&scope, &frame).ok();
+            }
+        }));
+
+        let mut animation = Self {
+            scope: scope.clone(),
+            document: None,
+            clock,
+            frame,
+            listeners: Vec::new(),
+        };
+
+        if let Scope::Window(window) = &scope {
+            let document = window.document().ok_or(Error::NoDocument)?;
+
+            let on_resize: Closure<dyn FnMut()> = Closure::new({
+                let stage = stage.clone();
+                move || stage.borrow_mut().fit_canvas()
+            });
+
+            let on_visibility: Closure<dyn FnMut()> = Closure::new({
+                let (scope, document, clock, frame) = (
+                    scope.clone(),
+                    document.clone(),
+                    animation.clock.clone(),
+                    animation.frame.clone(),
+                );
+
+                move || {
+                    if document.hidden() {
+                        suspend(&scope, &mut clock.borrow_mut());
+                    } else if !clock.borrow().paused {
+                        let _ = schedule(&scope, &frame, &mut clock.borrow_mut());
+                    }
+                }
+            });
+
+            animation.listen(window.clone().into(), "resize", on_resize)?;
+            animation.listen(document.clone().into(), "visibilitychange", on_visibility)?;
+            animation.document = Some(document);
+        }
+
+        stage.borrow_mut().fit_canvas();
+        if !animation.hidden() {
+            schedule(
+                &animation.scope,
+                &animation.frame,
+                &mut animation.clock.borrow_mut(),
+            )?;
+        }
+
+        Ok(animation)
+    }
+
+    /* Pause the animation until it is resumed. */
+    pub fn pause(&self) {
+        let mut clock = self.clock.borrow_mut();
+        clock.paused = true;
+
+        suspend(&self.scope, &mut clock);
+    }
+
+    /* Resume the animation after it was paused. */
+    pub fn resume(&self) -> Result<(), Error> {
+        let mut clock = self.clock.borrow_mut();
+        clock.paused = false;
+
+        if self.hidden() {
+            return Ok(());
+        }
+
+        schedule(&self.scope, &self.frame, &mut clock)
+    }
+
+    pub fn paused(&self) -> bool {
+        self.clock.borrow().paused
+    }
+
+    /* Check whether the page is hidden, a worker can't tell. */
+    fn hidden(&self) -> bool {
+        self.document.as_ref().is_some_and(Document::hidden)
+    }
+
+    /* Call the closure whenever the given event happens to the target, for
+     * as long as the animation runs. */
+    fn listen(
+        &mut self,
+        target: EventTarget,
+        event: &'static str,
+        closure: Closure<dyn FnMut()>,
+    ) -> Result<(), Error> {
+        target.add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())?;
+        self.listeners.push((target, event, closure));
+
+        Ok(())
+    }
+}
+
+impl Drop for Animation {
+    fn drop(&mut self) {
+        suspend(&self.scope, &mut self.clock.borrow_mut());
+
+        for (target, event, closure) in &self.listeners {
+            let _ =
+                target.remove_event_listener_with_callback(event, closure.as_ref().unchecked_ref());
+        }
+
+        /* The frame callback refers to itself, so it has to be dropped by
+         * hand. */
+        self.frame.borrow_mut().take();
+    }
+}
+
+/* Simulate the time which passed since the last frame in whole steps, spawn
+ * fireworks while ramping up, and draw the result. The frame time also
+ * decides the level of detail. */
+fn advance(clock: &mut Clock, stage: &mut Stage, elapsed: f64) {
+    stage.measure(elapsed);
+
+    let step_duration = 1000. / STEPS_PER_SECOND;
+    clock.lag = (clock.lag + elapsed).min(MAX_STEPS_PER_FRAME * step_duration);
+
+    while clock.lag >= step_duration {
+        stage.step();
+        clock.lag -= step_duration;
+    }
+
+    /* Spawn fireworks in regular intervals such that the maximum is reached
+     * in the ramp duration. Spawns are ignored once the canvas is full. */
+    clock.since_spawn += elapsed;
+    let max_fireworks = stage.max_fireworks();
+    if max_fireworks > 0 && clock.since_spawn >= RAMP_DURATION / max_fireworks as f64 {
+        stage.spawn();
+        clock.since_spawn = 0.;
+    }
+
+    stage.draw();
+}
+
+/* Request the next animation frame if none is pending. */
+fn schedule(scope: &Scope, frame: &FrameCallback, clock: &mut Clock) -> Result<(), Error> {
+    if clock.request.is_none() {
+        clock.last_time = None;
+        clock.request = Some(request_frame(scope, frame)?);
+    }
+
+    Ok(())
+}
+
+/* Cancel the pending animation frame. */
+fn suspend(scope: &Scope, clock: &mut Clock) {
+    if let Some(request) = clock.request.take() {
+        let _ = scope.cancel_animation_frame(request);
+    }
+}
+
+fn request_frame(scope: &Scope, frame: &FrameCallback) -> Result<i32, Error> {
+    match frame.borrow().as_ref() {
+        Some(callback) => Ok(scope.request_animation_frame(callback.as_ref().unchecked_ref())?),
+        None => Err(Error::Stopped),
+    }
+}
@@ -0,0 +1,204 @@
+use js_sys::Math;
+use wasm_bindgen::JsValue;
+use web_sys::{
+    AudioBuffer, AudioContext, AudioNode, AudioScheduledSourceNode, BiquadFilterType,
+    OscillatorType,
+};
+
+use crate::graphics::{SoundEvent, SoundKind};
+
+/* The time in seconds it takes sound to travel one pixel. Sound travels
+ * 343 metres per second, and a pixel is taken to be 30 centimetres. */
+const SECONDS_PER_PIXEL: f64 = 0.3 / 343.;
+
+const VOLUME: f64 = 0.5;
+
+/* The length of the noise all the explosions are made from. */
+const NOISE_SECONDS: u32 = 2;
+
+const WHISTLE_DURATION: f64 = 1.2;
+
+/* This struct plays the sounds of the simulation using the Web Audio API. */
+pub struct Audio {
+    context: Option<AudioContext>,
+    noise: Option<AudioBuffer>,
+    muted: bool,
+}
+
+impl Audio {
+    /* Create a muted audio backend. Browsers only allow starting audio after
+     * the user interacted with the page, so the audio context is only created
+     * when unmuting. */
+    pub fn new() -> Self {
+        Self {
+            context: None,
+            noise: None,
+            muted: true,
+        }
+    }
+
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    /* Mute or unmute the audio. */
+    pub fn set_muted(&mut self, muted: bool) -> Result<(), JsValue> {
+        self.muted = muted;
+
+        match &self.context {
+            Some(context) if muted => {
+                let _ = context.suspend()?;
+            }
+            Some(context) => {
+                let _ = context.resume()?;
+            }
+            None if !muted => {
+                let context = AudioContext::new()?;
+                self.noise = Some(noise_buffer(&context)?);
+                self.context = Some(context);
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /* Play sounds as heard by a listener at the bottom centre of a canvas of
+     * the given size. Sounds are panned by their horizontal position, and
+     * arrive later the further away they are. */
+    pub fn play(&self, events: &[SoundEvent], width: f64, height: f64) -> Result<(), JsValue> {
+        let (context, noise) = match (&self.context, &self.noise) {
+            (Some(context), Some(noise)) if !self.muted => (context, noise),
+            _ => return Ok(()),
+        };
+
+        for event in events {
+            let distance = (event.pos.x() - width / 2.).hypot(height - event.pos.y());
+            let when = context.current_time() + distance * SECONDS_PER_PIXEL;
+            let volume = VOLUME * event.intensity / (1. + distance / height);
+
+            let panner = context.create_stereo_panner()?;
+            panner
+                .pan()
+                .set_value((event.pos.x() / width * 2. - 1.).clamp(-1., 1.) as f32);
+            panner.connect_with_audio_node(&context.destination())?;
+
+            match event.kind {
+                SoundKind::Launch => play_noise(
+                    context,
+                    noise,
+                    &panner,
+                    when,
+                    BiquadFilterType::Bandpass,
+                    600.,
+                    0.4,
+                    volume * 0.3,
+                )?,
+                SoundKind::Whistle => play_whistle(context, &panner, when, volume * 0.1)?,
+                SoundKind::Burst => play_noise(
+                    context,
+                    noise,
+                    &panner,
+                    when,
+                    BiquadFilterType::Lowpass,
+                    400.,
+                    1.5,
+                    volume,
+                )?,
+                SoundKind::Crackle => play_noise(
+                    context,
+                    noise,
+                    &panner,
+                    when,
+                    BiquadFilterType::Highpass,
+                    3000.,
+                    0.04,
+                    volume * 0.4,
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/* Create a buffer filled with white noise. */
+fn noise_buffer(context: &AudioContext) -> Result<AudioBuffer, JsValue> {
+    let sample_rate = context.sample_rate();
+    let length = sample_rate as u32 * NOISE_SECONDS;
+
+    let samples: Vec<f32> = (0..length)
+        .map(|_| (Math::random() * 2. - 1.) as f32)
+        .collect();
+
+    let buffer = context.create_buffer(1, length, sample_rate)?;
+    buffer.copy_to_channel(&samples, 0)?;
+
+    Ok(buffer)
+}
+
+/* Play filtered noise which fades out over the given duration. */
+#[allow(clippy::too_many_arguments)]
+fn play_noise(
+    context: &AudioContext,
+    noise: &AudioBuffer,
+    output: &AudioNode,
+    when: f64,
+    filter_type: BiquadFilterType,
+    frequency: f32,
+    duration: f64,
+    volume: f64,
+) -> Result<(), JsValue> {
+    let source = context.create_buffer_source()?;
+    source.set_buffer(Some(noise));
+
+    let filter = context.create_biquad_filter()?;
+    filter.set_type(filter_type);
+    filter.frequency().set_value(frequency);
+
+    let gain = context.create_gain()?;
+    gain.gain().set_value_at_time(volume as f32, when)?;
+    gain.gain()
+        .exponential_ramp_to_value_at_time(0.001, when + duration)?;
+
+    source.connect_with_audio_node(&filter)?;
+    filter.connect_with_audio_node(&gain)?;
+    gain.connect_with_audio_node(output)?;
+
+    /* Start somewhere in the noise, so no two explosions sound the same. */
+    source.start_with_when_and_grain_offset(
+        when,
+        Math::random() * (NOISE_SECONDS as f64 - duration).max(0.),
+    )?;
+    AudioScheduledSourceNode::stop_with_when(&source, when + duration)?;
+
+    Ok(())
+}
+
+/* Play the rising whistle of a rocket going up. */
+fn play_whistle(
+    context: &AudioContext,
+    output: &AudioNode,
+    when: f64,
+    volume: f64,
+) -> Result<(), JsValue> {
+    let oscillator = context.create_oscillator()?;
+    oscillator.set_type(OscillatorType::Sine);
+    oscillator.frequency().set_value_at_time(1200., when)?;
+    oscillator
+        .frequency()
+        .exponential_ramp_to_value_at_time(2400., when + WHISTLE_DURATION)?;
+
+    let gain = context.create_gain()?;
+    gain.gain().set_value_at_time(volume as f32, when)?;
+    gain.gain()
+        .exponential_ramp_to_value_at_time(0.001, when + WHISTLE_DURATION)?;
+
+    oscillator.connect_with_audio_node(&gain)?;
+    gain.connect_with_audio_node(output)?;
+
+    oscillator.start_with_when(when)?;
+    oscillator.stop_with_when(when + WHISTLE_DURATION)?;
+
+    Ok(())
+}
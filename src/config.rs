@@ -0,0 +1,700 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::graphics::{FireworkKind, IntegratorKind, Landscape, Palette};
+
+/* How much detail is drawn, to keep slow devices running smoothly. */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    Low,
+    Medium,
+    High,
+}
+
+impl Quality {
+    /* Look up a quality level by the name it has in a configuration. */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "low" => Some(Quality::Low),
+            "medium" => Some(Quality::Medium),
+            "high" => Some(Quality::High),
+            _ => None,
+        }
+    }
+
+    /* The name of the quality level in a configuration. */
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quality::Low => "low",
+            Quality::Medium => "medium",
+            Quality::High => "high",
+        }
+    }
+
+    /* The fraction of the configured particles which is actually used. */
+    pub fn particle_scale(&self) -> f64 {
+        match self {
+            Quality::Low => 0.4,
+            Quality::Medium => 0.7,
+            Quality::High => 1.,
+        }
+    }
+}
+
+/* How often each type of firework is picked, relative to the others. */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FireworkWeights {
+    pub standard: f64,
+    pub colour_shift: f64,
+    pub sparkle: f64,
+}
+
+impl Default for FireworkWeights {
+    fn default() -> Self {
+        Self {
+            standard: 2.,
+            colour_shift: 1.,
+            sparkle: 1.,
+        }
+    }
+}
+
+impl FireworkWeights {
+    /* Pick a type of firework using a random number between 0 and 1. */
+    pub fn pick(&self, random: f64) -> FireworkKind {
+        let mut target = random * (self.standard + self.colour_shift + self.sparkle);
+
+        for (kind, weight) in [
+            (FireworkKind::Standard, self.standard),
+            (FireworkKind::ColourShift, self.colour_shift),
+        ] {
+            if target < weight {
+                return kind;
+            }
+            target -= weight;
+        }
+
+        FireworkKind::Sparkle
+    }
+}
+
+/* The settings of a firework show. */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /* The amount of particles in an explosion. */
+    pub particle_count: u32,
+
+    /* The amount of steps an explosion lasts. */
+    pub particle_lifetime: u32,
+
+    /* The vertical speed below which a rocket explodes. */
+    pub rocket_explode_speed: f64,
+
+    pub gravity: f64,
+
+    pub star_count: u32,
+
+    pub star_radius: f64,
+
+    /* The width of the canvas in pixels per firework. */
+    pub firework_spacing: f64,
+
+    pub palette: Palette,
+
+    pub quality: Quality,
+
+    pub firework_weights: FireworkWeights,
+
+    /* The length of the trails behind moving sparks, in steps of their
+     * movement. */
+    pub trail_length: f64,
+
+    /* Whether sparks have a soft glow around them. */
+    pub glow: bool,
+
+    /* How particles are moved forward in time. */
+    pub integrator: IntegratorKind,
+
+    /* The fraction of their speed sparks keep when bouncing off the ground
+     * or the walls. */
+    pub restitution: f64,
+
+    /* The fraction of their speed along the ground sparks lose when they
+     * bounce. */
+    pub friction: f64,
+
+    /* Whether sparks bounce off the sides of the canvas. */
+    pub walls: bool,
+
+    /* Whether explosions are spheres in space, seen through a camera. */
+    pub three_d: bool,
+
+    /* How fast the camera orbits the show in 3D, in degrees per second. */
+    pub orbit_speed: f64,
+
+    /* The silhouette along the bottom of the canvas. */
+    pub landscape: Landscape,
+
+    /* Whether the show is reflected in water along the bottom of the
+     * canvas. */
+    pub reflection: bool,
+
+    /* The fraction of the height of the canvas taken up by the water. */
+    pub water_level: f64,
+
+    /* Whether the sky lights up around bursts. */
+    pub sky_flash: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            particle_count: 20,
+            particle_lifetime: 70,
+            rocket_explode_speed: -2.,
+            gravity: 0.07,
+            star_count: 20,
+            star_radius: 2.,
+            firework_spacing: 100.,
+            palette: Palette::Random,
+            quality: Quality::High,
+            firework_weights: FireworkWeights::default(),
+            trail_length: 0.,
+            glow: false,
+            integrator: IntegratorKind::SemiImplicitEuler,
+            restitution: 0.3,
+            friction: 0.2,
+            walls: false,
+            three_d: false,
+            orbit_speed: 3.,
+            landscape: Landscape::None,
+            reflection: false,
+            water_level: 0.2,
+            sky_flash: true,
+        }
+    }
+}
+
+impl Settings {
+    /* Check that the settings make for a working show. */
+    pub fn validate(&self) -> Result<(), String> {
+        if self.particle_count == 0 || self.particle_count > 2000 {
+            return Err(format!(
+                "particle_count should be between 1 and 2000, not {}.",
+                self.particle_count
+            ));
+        }
+
+        if self.particle_lifetime == 0 || self.particle_lifetime > 10000 {
+            return Err(format!(
+                "particle_lifetime should be between 1 and 10000, not {}.",
+                self.particle_lifetime
+            ));
+        }
+
+        if !(self.rocket_explode_speed.is_finite() && self.rocket_explode_speed < 0.) {
+            return Err(format!(
+                "rocket_explode_speed should be negative, not {}.",
+                self.rocket_explode_speed
+            ));
+        }
+
+        if !(self.gravity.is_finite() && self.gravity > 0.) {
+            return Err(format!("gravity should be positive, not {}.", self.gravity));
+        }
+
+        if self.star_count > 1000 {
+            return Err(format!(
+                "star_count should be at most 1000, not {}.",
+                self.star_count
+            ));
+        }
+
+        if !(self.star_radius.is_finite() && self.star_radius > 0.) {
+            return Err(format!(
+                "star_radius should be positive, not {}.",
+                self.star_radius
+            ));
+        }
+
+        if !(self.firework_spacing.is_finite() && self.firework_spacing >= 1.) {
+            return Err(format!(
+                "firework_spacing should be at least 1, not {}.",
+                self.firework_spacing
+            ));
+        }
+
+        if !(self.trail_length.is_finite() && (0. ..=50.).contains(&self.trail_length)) {
+            return Err(format!(
+                "trail_length should be between 0 and 50, not {}.",
+                self.trail_length
+            ));
+        }
+
+        if !(0. ..=1.).contains(&self.restitution) {
+            return Err(format!(
+                "restitution should be between 0 and 1, not {}.",
+                self.restitution
+            ));
+        }
+
+        if !(0. ..=1.).contains(&self.friction) {
+            return Err(format!(
+                "friction should be between 0 and 1, not {}.",
+                self.friction
+            ));
+        }
+
+        if !(self.orbit_speed.is_finite() && self.orbit_speed.abs() <= 90.) {
+            return Err(format!(
+                "orbit_speed should be between -90 and 90, not {}.",
+                self.orbit_speed
+            ));
+        }
+
+        if !(0.05..=0.5).contains(&self.water_level) {
+            return Err(format!(
+                "water_level should be between 0.05 and 0.5, not {}.",
+                self.water_level
+            ));
+        }
+
+        let weights = [
+            self.firework_weights.standard,
+            self.firework_weights.colour_shift,
+            self.firework_weights.sparkle,
+        ];
+        if weights
+            .iter()
+            .any(|weight| !(weight.is_finite() && *weight >= 0.))
+            || weights.iter().sum::<f64>() <= 0.
+        {
+            return Err(
+                "firework_weights should not be negative, and not all be zero.".to_string(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /* The amount of particles in an explosion at the current quality. */
+    pub fn particles(&self) -> u32 {
+        ((self.particle_count as f64 * self.quality.particle_scale()).round() as u32).max(1)
+    }
+
+    /* The height of the water line on a canvas of the given height, if the
+     * show is reflected. */
+    pub fn water_line(&self, height: u32) -> Option<f64> {
+        self.reflection
+            .then_some(height as f64 * (1. - self.water_level))
+    }
+
+    /* The maximal amount of fireworks on a canvas of the given width. */
+    pub fn max_fireworks(&self, width: u32) -> u32 {
+        (width as f64 / self.firework_spacing).ceil() as u32
+    }
+}
+
+/* The configuration of a running show. All copies of a config share the same
+ * settings, so changes made from JavaScript apply to the show immediately. */
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct Config {
+    settings: Rc<RefCell<Settings>>,
+}
+
+impl Config {
+    /* Get a copy of the current settings. */
+    pub fn settings(&self) -> Settings {
+        *self.settings.borrow()
+    }
+
+    /* Change the settings if the result is valid. */
+    pub fn update(&self, change: impl FnOnce(&mut Settings)) -> Result<(), JsValue> {
+        let mut settings = self.settings();
+        change(&mut settings);
+        settings.validate().map_err(|err| JsValue::from_str(&err))?;
+
+        *self.settings.borrow_mut() = settings;
+
+        Ok(())
+    }
+
+    /* Change the settings in a JSON object, keeping the others. */
+    pub fn merge(&self, changes: &Map<String, Value>) -> Result<(), JsValue> {
+        let mut json = serde_json::to_value(self.settings()).unwrap();
+        if let Value::Object(current) = &mut json {
+            current.extend(changes.clone());
+        }
+
+        let settings: Settings =
+            serde_json::from_value(json).map_err(|err| JsValue::from_str(&err.to_string()))?;
+
+        self.update(|current| *current = settings)
+    }
+}
+
+#[wasm_bindgen]
+impl Config {
+    /* Create a configuration with the default settings. */
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Config {
+        Config::default()
+    }
+
+    /* Serialize the settings to JSON. */
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.settings()).unwrap()
+    }
+
+    /* Replace the settings by those in a JSON object. Settings missing from
+     * the object are reset to their defaults. */
+    pub fn load_json(&self, json: &str) -> Result<(), JsValue> {
+        let settings: Settings =
+            serde_json::from_str(json).map_err(|err| JsValue::from_str(&err.to_string()))?;
+
+        self.update(|current| *current = settings)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn particle_count(&self) -> u32 {
+        self.settings().particle_count
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_particle_count(&self, value: u32) -> Result<(), JsValue> {
+        self.update(|settings| settings.particle_count = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn particle_lifetime(&self) -> u32 {
+        self.settings().particle_lifetime
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_particle_lifetime(&self, value: u32) -> Result<(), JsValue> {
+        self.update(|settings| settings.particle_lifetime = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn rocket_explode_speed(&self) -> f64 {
+        self.settings().rocket_explode_speed
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_rocket_explode_speed(&self, value: f64) -> Result<(), JsValue> {
+        self.update(|settings| settings.rocket_explode_speed = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn gravity(&self) -> f64 {
+        self.settings().gravity
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_gravity(&self, value: f64) -> Result<(), JsValue> {
+        self.update(|settings| settings.gravity = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn star_count(&self) -> u32 {
+        self.settings().star_count
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_star_count(&self, value: u32) -> Result<(), JsValue> {
+        self.update(|settings| settings.star_count = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn star_radius(&self) -> f64 {
+        self.settings().star_radius
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_star_radius(&self, value: f64) -> Result<(), JsValue> {
+        self.update(|settings| settings.star_radius = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn firework_spacing(&self) -> f64 {
+        self.settings().firework_spacing
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_firework_spacing(&self, value: f64) -> Result<(), JsValue> {
+        self.update(|settings| settings.firework_spacing = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn palette(&self) -> String {
+        self.settings().palette.name().to_string()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_palette(&self, value: String) -> Result<(), JsValue> {
+        let palette = Palette::from_name(&value)
+            .ok_or_else(|| JsValue::from_str(&format!("There is no palette called {}.", value)))?;
+
+        self.update(|settings| settings.palette = palette)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn quality(&self) -> String {
+        self.settings().quality.name().to_string()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_quality(&self, value: String) -> Result<(), JsValue> {
+        let quality = Quality::from_name(&value).ok_or_else(|| {
+            JsValue::from_str(&format!("There is no quality level called {}.", value))
+        })?;
+
+        self.update(|settings| settings.quality = quality)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn trail_length(&self) -> f64 {
+        self.settings().trail_length
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_trail_length(&self, value: f64) -> Result<(), JsValue> {
+        self.update(|settings| settings.trail_length = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn glow(&self) -> bool {
+        self.settings().glow
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_glow(&self, value: bool) -> Result<(), JsValue> {
+        self.update(|settings| settings.glow = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn integrator(&self) -> String {
+        self.settings().integrator.name().to_string()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_integrator(&self, value: String) -> Result<(), JsValue> {
+        let integrator = IntegratorKind::from_name(&value).ok_or_else(|| {
+            JsValue::from_str(&format!("There is no integrator called {}.", value))
+        })?;
+
+        self.update(|settings| settings.integrator = integrator)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn restitution(&self) -> f64 {
+        self.settings().restitution
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_restitution(&self, value: f64) -> Result<(), JsValue> {
+        self.update(|settings| settings.restitution = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn friction(&self) -> f64 {
+        self.settings().friction
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_friction(&self, value: f64) -> Result<(), JsValue> {
+        self.update(|settings| settings.friction = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn walls(&self) -> bool {
+        self.settings().walls
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_walls(&self, value: bool) -> Result<(), JsValue> {
+        self.update(|settings| settings.walls = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn three_d(&self) -> bool {
+        self.settings().three_d
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_three_d(&self, value: bool) -> Result<(), JsValue> {
+        self.update(|settings| settings.three_d = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn orbit_speed(&self) -> f64 {
+        self.settings().orbit_speed
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_orbit_speed(&self, value: f64) -> Result<(), JsValue> {
+        self.update(|settings| settings.orbit_speed = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn landscape(&self) -> String {
+        self.settings().landscape.name().to_string()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_landscape(&self, value: String) -> Result<(), JsValue> {
+        let landscape = Landscape::from_name(&value).ok_or_else(|| {
+            JsValue::from_str(&format!("There is no landscape called {}.", value))
+        })?;
+
+        self.update(|settings| settings.landscape = landscape)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn reflection(&self) -> bool {
+        self.settings().reflection
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_reflection(&self, value: bool) -> Result<(), JsValue> {
+        self.update(|settings| settings.reflection = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn water_level(&self) -> f64 {
+        self.settings().water_level
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_water_level(&self, value: f64) -> Result<(), JsValue> {
+        self.update(|settings| settings.water_level = value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sky_flash(&self) -> bool {
+        self.settings().sky_flash
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_sky_flash(&self, value: bool) -> Result<(), JsValue> {
+        self.update(|settings| settings.sky_flash = value)
+    }
+
+    /* Set how often each type of firework is picked, relative to the others. */
+    pub fn set_firework_weights(
+        &self,
+        standard: f64,
+        colour_shift: f64,
+        sparkle: f64,
+    ) -> Result<(), JsValue> {
+        self.update(|settings| {
+            settings.firework_weights = FireworkWeights {
+                standard,
+                colour_shift,
+                sparkle,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn default_settings_are_valid() {
+        assert_eq!(Settings::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_settings_are_refused() {
+        let changes: [fn(&mut Settings); 8] = [
+            |settings| settings.particle_count = 0,
+            |settings| settings.rocket_explode_speed = 1.,
+            |settings| settings.gravity = f64::NAN,
+            |settings| settings.firework_spacing = 0.5,
+            |settings| settings.trail_length = 51.,
+            |settings| settings.orbit_speed = -180.,
+            |settings| settings.water_level = 0.9,
+            |settings| {
+                settings.firework_weights = FireworkWeights {
+                    standard: 0.,
+                    colour_shift: 0.,
+                    sparkle: 0.,
+                }
+            },
+        ];
+
+        for change in changes {
+            let mut settings = Settings::default();
+            change(&mut settings);
+
+            assert!(settings.validate().is_err(), "{:?} is valid", settings);
+        }
+    }
+
+    #[test]
+    fn quality_scales_the_particles() {
+        let mut settings = Settings {
+            particle_count: 20,
+            ..Settings::default()
+        };
+        assert_eq!(settings.particles(), 20);
+
+        settings.quality = Quality::Low;
+        assert_eq!(settings.particles(), 8);
+
+        /* There is always at least one particle. */
+        settings.particle_count = 1;
+        assert_eq!(settings.particles(), 1);
+    }
+
+    #[test]
+    fn fireworks_are_spaced_over_the_width() {
+        let settings = Settings::default();
+
+        assert_eq!(settings.max_fireworks(1000), 10);
+        assert_eq!(settings.max_fireworks(1001), 11);
+        assert_eq!(settings.max_fireworks(1), 1);
+    }
+
+    #[test]
+    fn weights_pick_kinds_in_proportion() {
+        let weights = FireworkWeights::default();
+
+        assert_eq!(weights.pick(0.), FireworkKind::Standard);
+        assert_eq!(weights.pick(0.49), FireworkKind::Standard);
+        assert_eq!(weights.pick(0.5), FireworkKind::ColourShift);
+        assert_eq!(weights.pick(0.74), FireworkKind::ColourShift);
+        assert_eq!(weights.pick(0.75), FireworkKind::Sparkle);
+        assert_eq!(weights.pick(0.99), FireworkKind::Sparkle);
+    }
+
+    proptest! {
+        #[test]
+        fn weights_never_pick_an_unweighted_kind(
+            standard in prop_oneof![Just(0.), 0.01..10f64],
+            colour_shift in prop_oneof![Just(0.), 0.01..10f64],
+            sparkle in 0.01..10f64,
+            random in 0. ..1f64,
+        ) {
+            let weights = FireworkWeights { standard, colour_shift, sparkle };
+
+            match weights.pick(random) {
+                FireworkKind::Standard => prop_assert!(standard > 0.),
+                FireworkKind::ColourShift => prop_assert!(colour_shift > 0.),
+                FireworkKind::Sparkle => prop_assert!(sparkle > 0.),
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt;
+
+use wasm_bindgen::JsValue;
+
+/* The things which can go wrong while starting a show. */
+#[derive(Debug)]
+pub enum Error {
+    NoWindow,
+    NoDocument,
+    NoBody,
+    /* The element with the given id is not a canvas. */
+    NotACanvas(String),
+    /* The canvas can't be drawn on, because it already has another kind of
+     * context or the browser doesn't support it. */
+    NoContext,
+    NoWindowSize,
+    /* The browser doesn't support WebGL 2, or it was lost. */
+    NoWebGl,
+    /* A shader could not be compiled or linked, with the log of the driver. */
+    Shader(String),
+    /* The animation loop of a show was stopped. */
+    Stopped,
+    /* A call to the browser failed with the given exception. */
+    Js(JsValue),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoWindow => write!(f, "There is no window to show the fireworks in."),
+            Error::NoDocument => write!(f, "The window has no document."),
+            Error::NoBody => write!(f, "The document has no body to add a canvas to."),
+            Error::NotACanvas(id) => write!(f, "The element with id {:?} is not a canvas.", id),
+            Error::NoContext => write!(f, "Could not get a 2d context for the canvas."),
+            Error::NoWindowSize => write!(f, "Could not get the size of the window."),
+            Error::NoWebGl => write!(f, "WebGL 2 is not available."),
+            Error::Shader(log) => write!(f, "Could not build a shader: {}", log),
+            Error::Stopped => write!(f, "The animation was stopped."),
+            Error::Js(err) => write!(f, "The browser reported an error: {:?}", err),
+        }
+    }
+}
+
+impl From<JsValue> for Error {
+    fn from(err: JsValue) -> Self {
+        Error::Js(err)
+    }
+}
+
+/* Errors are thrown as JavaScript errors with a descriptive message. */
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
@@ -0,0 +1,262 @@
+use crate::graphics::Detail;
+
+/* The levels of detail the governor chooses from, from the least to the
+ * most detailed. */
+const LEVELS: [Detail; 4] = [
+    Detail {
+        particles: 0.3,
+        fireworks: 0.5,
+        trails: 0.,
+        glow: false,
+        sky_flash: false,
+    },
+    Detail {
+        particles: 0.5,
+        fireworks: 0.7,
+        trails: 0.5,
+        glow: false,
+        sky_flash: false,
+    },
+    Detail {
+        particles: 0.75,
+        fireworks: 0.85,
+        trails: 1.,
+        glow: false,
+        sky_flash: true,
+    },
+    Detail::FULL,
+];
+
+const DEFAULT_TARGET_FPS: f64 = 50.;
+
+/* How much weight a new frame time gets in the average. */
+const SMOOTHING: f64 = 0.1;
+
+/* Frame times above the target by this factor are too slow, and below the
+ * target by this factor are fast enough to try more detail. */
+const SLOW_MARGIN: f64 = 1.25;
+const FAST_MARGIN: f64 = 1.1;
+
+/* How long in milliseconds frames have to be slow before lowering the
+ * detail. */
+const LOWER_DELAY: f64 = 1000.;
+
+/* How long in milliseconds frames have to be fast before raising the detail.
+ * The delay doubles every time the detail had to be lowered, so the show
+ * settles instead of switching back and forth. */
+const RAISE_DELAY: f64 = 3000.;
+const MAX_RAISE_DELAY: f64 = 60000.;
+
+/* Frames longer than this are interruptions rather than slowness. */
+const MAX_FRAME_TIME: f64 = 1000.;
+
+/* This struct measures how long frames take, and lowers the detail of the
+ * show when it can't keep up with the target frame rate. */
+pub struct Governor {
+    enabled: bool,
+    target: f64,
+    level: usize,
+    average: Option<f64>,
+    slow_time: f64,
+    fast_time: f64,
+    raise_delay: f64,
+}
+
+impl Governor {
+    /* Create a governor which starts out at full detail. */
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            target: 1000. / DEFAULT_TARGET_FPS,
+            level: LEVELS.len() - 1,
+            average: None,
+            slow_time: 0.,
+            fast_time: 0.,
+            raise_delay: RAISE_DELAY,
+        }
+    }
+
+    /* Take the duration of a frame in milliseconds into account. Returns
+     * whether the level of detail changed. */
+    pub fn measure(&mut self, frame_time: f64) -> bool {
+        if !(self.enabled && frame_time > 0. && frame_time < MAX_FRAME_TIME) {
+            return false;
+        }
+
+        let average = match self.average {
+            Some(average) => average + (frame_time - average) * SMOOTHING,
+            None => frame_time,
+        };
+        self.average = Some(average);
+
+        if average > self.target * SLOW_MARGIN {
+            self.fast_time = 0.;
+            self.slow_time += frame_time;
+
+            if self.slow_time >= LOWER_DELAY && self.level > 0 {
+                self.level -= 1;
+                self.raise_delay = (self.raise_delay * 2.).min(MAX_RAISE_DELAY);
+                self.settle();
+
+                return true;
+            }
+        } else if average < self.target * FAST_MARGIN {
+            self.slow_time = 0.;
+            self.fast_time += frame_time;
+
+            if self.fast_time >= self.raise_delay && self.level < LEVELS.len() - 1 {
+                self.level += 1;
+                self.settle();
+
+                return true;
+            }
+        } else {
+            self.slow_time = 0.;
+            self.fast_time = 0.;
+        }
+
+        false
+    }
+
+    /* The current level of detail, 0 being the lowest. */
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /* The highest level of detail. */
+    pub fn max_level(&self) -> usize {
+        LEVELS.len() - 1
+    }
+
+    /* Get the detail at the given level, or the highest level. */
+    pub fn level_detail(level: usize) -> Detail {
+        LEVELS[level.min(LEVELS.len() - 1)]
+    }
+
+    /* Turn the governor on or off. When it is off, everything is drawn in
+     * full detail. */
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.level = LEVELS.len() - 1;
+        self.raise_delay = RAISE_DELAY;
+        self.settle();
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /* Set the frame rate the governor tries to keep. */
+    pub fn set_target_fps(&mut self, fps: f64) -> Result<(), String> {
+        if !(fps.is_finite() && fps > 0.) {
+            return Err(format!(
+                "The target frame rate should be positive, not {}.",
+                fps
+            ));
+        }
+
+        self.target = 1000. / fps;
+        self.settle();
+
+        Ok(())
+    }
+
+    /* Start measuring anew after the detail changed. */
+    fn settle(&mut self) {
+        self.average = None;
+        self.slow_time = 0.;
+        self.fast_time = 0.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+
+    /* Measure frames of the given duration for the given time, and return
+     * the levels the governor switched to. */
+    fn run(governor: &mut Governor, frame_time: f64, duration: f64) -> Vec<usize> {
+        let mut levels = Vec::new();
+
+        for _ in 0..(duration / frame_time) as u32 {
+            if governor.measure(frame_time) {
+                levels.push(governor.level());
+            }
+        }
+
+        levels
+    }
+
+    #[test]
+    fn slow_frames_lower_the_detail_a_level_at_a_time() {
+        let mut governor = Governor::new();
+
+        /* Half a second of slow frames is not enough. */
+        assert!(run(&mut governor, 40., 500.).is_empty());
+        assert_eq!(run(&mut governor, 40., 600.), vec![2]);
+        assert_eq!(run(&mut governor, 40., 2200.), vec![1, 0]);
+
+        /* There is no level below the lowest. */
+        assert!(run(&mut governor, 40., 5000.).is_empty());
+        assert_eq!(governor.level(), 0);
+
+        /* The lowest level leaves out everything which can be left out. */
+        let settings = Governor::level_detail(0).apply(Settings {
+            trail_length: 2.,
+            glow: true,
+            ..Settings::default()
+        });
+        assert_eq!(settings.trail_length, 0.);
+        assert!(!settings.glow && !settings.sky_flash);
+    }
+
+    #[test]
+    fn fast_frames_raise_the_detail_ever_more_slowly() {
+        let mut governor = Governor::new();
+        run(&mut governor, 40., 1000.);
+
+        /* After lowering once, it takes twice as long to raise again. */
+        assert!(run(&mut governor, 10., 5900.).is_empty());
+        assert_eq!(run(&mut governor, 10., 200.), vec![3]);
+
+        /* Lowering again doubles the delay again. The average takes a few
+         * frames to follow the frame times. */
+        assert_eq!(run(&mut governor, 40., 1500.), vec![2]);
+        assert!(run(&mut governor, 10., 11900.).is_empty());
+        assert_eq!(run(&mut governor, 10., 200.), vec![3]);
+    }
+
+    #[test]
+    fn frames_close_to_the_target_keep_the_detail() {
+        let mut governor = Governor::new();
+        run(&mut governor, 40., 1000.);
+
+        /* Between the margins, neither slow nor fast frames add up. */
+        assert!(run(&mut governor, 22., 60000.).is_empty());
+        assert_eq!(governor.level(), 2);
+    }
+
+    #[test]
+    fn interruptions_and_disabling_are_ignored() {
+        let mut governor = Governor::new();
+        assert!(run(&mut governor, 5000., 50000.).is_empty());
+
+        governor.set_enabled(false);
+        assert!(run(&mut governor, 40., 5000.).is_empty());
+        assert_eq!(governor.level(), governor.max_level());
+    }
+
+    #[test]
+    fn the_target_frame_rate_should_be_positive() {
+        let mut governor = Governor::new();
+
+        assert!(governor.set_target_fps(0.).is_err());
+        assert!(governor.set_target_fps(f64::NAN).is_err());
+
+        /* At 20 frames per second, 40 ms frames are fast enough. */
+        governor.set_target_fps(20.).unwrap();
+        assert!(run(&mut governor, 40., 5000.).is_empty());
+        assert_eq!(Governor::level_detail(10), Detail::FULL);
+    }
+}
@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+
+use super::random::Random;
+
+/* The amount of simulation steps per second, a step simulates 10 ms. */
+pub const STEPS_PER_SECOND: f64 = 100.;
+
+/* A beat, and how far between the shortest and longest flight its rocket
+ * should fly. */
+#[derive(Clone, Serialize, Deserialize)]
+struct Beat {
+    step: u64,
+    #[serde(with = "crate::graphics::float")]
+    flight: f64,
+}
+
+/* This struct launches rockets in time so that they explode on the beat. */
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BeatScheduler {
+    beats: Vec<Beat>,
+    step: u64,
+}
+
+impl BeatScheduler {
+    /* Create a scheduler for beats at the given times in seconds. */
+    pub fn new(random: &mut Random, times: &[f64]) -> Self {
+        let mut steps: Vec<u64> = times
+            .iter()
+            .filter(|time| time.is_finite() && **time >= 0.)
+            .map(|time| (time * STEPS_PER_SECOND).round() as u64)
+            .collect();
+        steps.sort_unstable();
+
+        Self {
+            beats: steps
+                .into_iter()
+                .map(|step| Beat {
+                    step,
+                    flight: random.uniform(),
+                })
+                .collect(),
+            step: 0,
+        }
+    }
+
+    /* Jump to the given time in seconds, for instance to stay in sync with
+     * the music when frames are dropped. */
+    pub fn sync(&mut self, time: f64) {
+        self.step = (time.max(0.) * STEPS_PER_SECOND).round() as u64;
+    }
+
+    /* Check whether all beats have been launched. */
+    pub fn finished(&self) -> bool {
+        self.beats.is_empty()
+    }
+
+    /* Advance one step, and return the flight times in steps of the rockets
+     * which should be launched now to explode on their beat. Beats which can
+     * no longer be reached by the fastest rocket are skipped. */
+    pub fn step(&mut self, min_flight: u64, max_flight: u64) -> Vec<u64> {
+        let now = self.step;
+        let mut launches = Vec::new();
+
+        self.beats.retain(|beat| {
+            if beat.step < now + min_flight {
+                return false;
+            }
+
+            let flight = min_flight + ((max_flight - min_flight) as f64 * beat.flight) as u64;
+            let remaining = beat.step - now;
+
+            if remaining > flight {
+                return true;
+            }
+
+            launches.push(remaining);
+            false
+        });
+
+        self.step += 1;
+
+        launches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fireworks::{flight_range, launch_vel, Firework, StandardFirework};
+    use super::super::sim::Fields;
+    use super::*;
+    use crate::config::Settings;
+
+    const WIDTH: u32 = 800;
+    const HEIGHT: u32 = 600;
+
+    /* Run the beats of the given times like a show does, and return the
+     * steps in which the rockets exploded. */
+    fn explosions(times: &[f64], steps: u64) -> Vec<u64> {
+        let settings = Settings::default();
+        let mut random = Random::seeded(1);
+        let mut scheduler = BeatScheduler::new(&mut random, times);
+        let (min_flight, max_flight) = flight_range(&settings, HEIGHT);
+
+        let mut rockets: Vec<StandardFirework> = Vec::new();
+        let mut explosions = Vec::new();
+
+        for step in 0..steps {
+            /* Rockets are left out once they exploded, so they aren't
+             * launched again. */
+            rockets.retain_mut(|rocket| {
+                rocket.step(&settings, &mut random, &Fields::default(), WIDTH, HEIGHT);
+
+                let exploded = rocket.burst(&settings).is_some();
+                if exploded {
+                    explosions.push(step);
+                }
+                !exploded
+            });
+
+            for flight in scheduler.step(min_flight, max_flight) {
+                let mut rocket = StandardFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+                rocket.launch(
+                    &settings,
+                    &mut random,
+                    400.,
+                    WIDTH,
+                    HEIGHT,
+                    launch_vel(&settings, flight),
+                );
+                rockets.push(rocket);
+            }
+        }
+
+        explosions
+    }
+
+    #[test]
+    fn rockets_explode_on_the_beat() {
+        assert_eq!(explosions(&[1., 2.5, 2.75], 400), vec![100, 250, 275]);
+    }
+
+    #[test]
+    fn unreachable_beats_are_skipped() {
+        /* No rocket is fast enough for a beat right at the start. */
+        assert_eq!(explosions(&[0.01, 3., -1., f64::NAN], 400), vec![300]);
+    }
+
+    #[test]
+    fn syncing_jumps_to_the_music() {
+        let mut scheduler = BeatScheduler::new(&mut Random::new(), &[10.]);
+        scheduler.sync(9.5);
+
+        /* Half a second before the beat, its rocket is launched in time. */
+        let (min_flight, max_flight) = (20, 80);
+        let launches: Vec<u64> = (0..50)
+            .flat_map(|_| scheduler.step(min_flight, max_flight))
+            .collect();
+
+        assert_eq!(launches.len(), 1);
+        assert!((min_flight..=max_flight).contains(&launches[0]));
+        assert!(scheduler.finished());
+    }
+}
@@ -0,0 +1,121 @@
+use std::f64::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+use super::beats::STEPS_PER_SECOND;
+use super::render::Sprite;
+use super::sim::{ThreeVec, TwoVec};
+
+/* The distance of the camera in front of the canvas, relative to the largest
+ * side of the canvas. */
+const DISTANCE: f64 = 1.5;
+
+/* Sprites closer to the camera than this fraction of its distance would
+ * cover the canvas, so they are left out. */
+const NEAR: f64 = 0.1;
+
+/* A camera looking at the canvas from in front of its middle, which orbits
+ * around the vertical axis through the middle of the canvas. Before it has
+ * moved, everything in the plane of the canvas is projected onto itself. */
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Camera {
+    /* How far the camera has orbited, in radians. */
+    #[serde(with = "crate::graphics::float")]
+    angle: f64,
+}
+
+impl Camera {
+    /* Orbit for one step at the given speed in degrees per second. */
+    pub fn orbit(&mut self, speed: f64) {
+        self.angle = (self.angle + speed.to_radians() / STEPS_PER_SECOND).rem_euclid(TAU);
+    }
+
+    /* Project a position in space onto a canvas of the given size, along
+     * with how much larger things there are drawn. Positions too close to
+     * or behind the camera are not seen. */
+    pub fn project_pos(&self, pos: &ThreeVec, width: u32, height: u32) -> Option<(TwoVec, f64)> {
+        let distance = DISTANCE * width.max(height) as f64;
+        let centre = TwoVec::new(width as f64 / 2., height as f64 / 2.);
+
+        let relative = ThreeVec::from_plane(pos.xy() - centre, pos.z()).rotate_y(-self.angle);
+        let scale = distance / (distance + relative.z());
+
+        (distance + relative.z() >= NEAR * distance)
+            .then(|| (centre + relative.xy() * scale, scale))
+    }
+
+    /* Project a sprite in space onto a canvas of the given size. Sprites get
+     * larger towards the camera, and dimmer away from it. Sprites too close
+     * to or behind the camera are not drawn. */
+    pub fn project(&self, sprite: &Sprite, width: u32, height: u32) -> Option<Sprite> {
+        let project = |pos: TwoVec, depth: f64| {
+            self.project_pos(&ThreeVec::from_plane(pos, depth), width, height)
+        };
+
+        let (from, scale) = project(sprite.from, sprite.from_depth)?;
+        let (to, _) = project(sprite.to, sprite.to_depth)?;
+
+        Some(Sprite {
+            from,
+            to,
+            radius: sprite.radius * scale,
+            alpha: sprite.alpha * scale.min(1.),
+            from_depth: 0.,
+            to_depth: 0.,
+            ..*sprite
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot(x: f64, y: f64, depth: f64) -> Sprite {
+        Sprite::dot(TwoVec::new(x, y), 2., (255, 255, 255), 1.).at_depth(depth, depth)
+    }
+
+    #[test]
+    fn the_canvas_is_projected_onto_itself() {
+        let sprite = Camera::default()
+            .project(&dot(30., 70., 0.), 200, 100)
+            .unwrap();
+
+        assert_eq!(
+            (sprite.from, sprite.radius, sprite.alpha),
+            (TwoVec::new(30., 70.), 2., 1.)
+        );
+    }
+
+    #[test]
+    fn depth_shrinks_and_dims() {
+        let camera = Camera::default();
+        let near = camera.project(&dot(50., 50., -100.), 200, 100).unwrap();
+        let far = camera.project(&dot(50., 50., 300.), 200, 100).unwrap();
+
+        /* The camera is 300 pixels away, so twice as far is half as big. */
+        assert_eq!(
+            (far.from, far.radius, far.alpha),
+            (TwoVec::new(75., 50.), 1., 0.5)
+        );
+        assert_eq!((near.radius, near.alpha), (3., 1.));
+        assert_eq!(near.from, TwoVec::new(25., 50.));
+        assert!(camera.project(&dot(50., 50., -290.), 200, 100).is_none());
+    }
+
+    #[test]
+    fn orbiting_turns_depth_sideways() {
+        let mut camera = Camera::default();
+        for _ in 0..STEPS_PER_SECOND as u32 {
+            camera.orbit(90.);
+        }
+
+        /* Looking from the side, a point behind the middle of the canvas is
+         * seen beside it, and the canvas itself on edge. */
+        let behind = camera.project(&dot(100., 50., 50.), 200, 100).unwrap();
+        let side = camera.project(&dot(150., 50., 0.), 200, 100).unwrap();
+
+        assert!((behind.from.x() - 150.).abs() < 1e-9 && behind.from_depth == 0.);
+        assert!((side.from.x() - 100.).abs() < 1e-9);
+    }
+}
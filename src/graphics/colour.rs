@@ -1,25 +1,92 @@
-use js_sys::Math;
+use serde::{Deserialize, Serialize};
+
+use super::random::Random;
 
 pub type Colour = (u8, u8, u8);
 
 pub const YELLOW: Colour = (200, 200, 0);
 pub const ORANGE: Colour = (255, 200, 0);
 
+const GOLD: [Colour; 3] = [(255, 215, 0), (255, 190, 60), (240, 230, 140)];
+const NEON: [Colour; 5] = [
+    (255, 0, 128),
+    (0, 255, 255),
+    (128, 255, 0),
+    (255, 0, 255),
+    (255, 255, 0),
+];
+const DUTCH: [Colour; 4] = [(174, 28, 40), (255, 255, 255), (33, 70, 139), (255, 120, 0)];
+
+/* The sets of colours fireworks can be given. */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Palette {
+    Random,
+    Gold,
+    Pastel,
+    Neon,
+    Dutch,
+}
+
+impl Palette {
+    /* Look up a palette by the name it has in a configuration. */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "random" => Some(Palette::Random),
+            "gold" => Some(Palette::Gold),
+            "pastel" => Some(Palette::Pastel),
+            "neon" => Some(Palette::Neon),
+            "dutch" => Some(Palette::Dutch),
+            _ => None,
+        }
+    }
+
+    /* The name of the palette in a configuration. */
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Random => "random",
+            Palette::Gold => "gold",
+            Palette::Pastel => "pastel",
+            Palette::Neon => "neon",
+            Palette::Dutch => "dutch",
+        }
+    }
+
+    /* Generate a random colour from the palette. */
+    pub fn random_colour(&self, random: &mut Random) -> Colour {
+        match self {
+            Palette::Random => random_colour(random),
+            Palette::Gold => pick(random, &GOLD),
+            /* Mix a random colour with white. */
+            Palette::Pastel => {
+                colour_add(&colour_mul(&random_colour(random), 0.4), &(150, 150, 150))
+            }
+            Palette::Neon => pick(random, &NEON),
+            Palette::Dutch => pick(random, &DUTCH),
+        }
+    }
+}
+
+/* Pick one of the given colours at random. */
+fn pick(random: &mut Random, colours: &[Colour]) -> Colour {
+    colours[((random.uniform() * colours.len() as f64) as usize).min(colours.len() - 1)]
+}
+
 /* Generate a random colour. */
-pub fn random_colour() -> Colour {
+pub fn random_colour(random: &mut Random) -> Colour {
     (
-        (Math::random() * 255.) as u8,
-        (Math::random() * 255.) as u8,
-        (Math::random() * 255.) as u8,
+        (random.uniform() * 255.) as u8,
+        (random.uniform() * 255.) as u8,
+        (random.uniform() * 255.) as u8,
     )
 }
 
-/* Add two colours together. */
+/* Add two colours together, saturating at full brightness. */
 pub fn colour_add(c1: &Colour, c2: &Colour) -> Colour {
     (
-        c1.0.wrapping_add(c2.0),
-        c1.1.wrapping_add(c2.1),
-        c1.2.wrapping_add(c2.2),
+        c1.0.saturating_add(c2.0),
+        c1.1.saturating_add(c2.1),
+        c1.2.saturating_add(c2.2),
     )
 }
 
@@ -31,3 +98,70 @@ pub fn colour_mul(colour: &Colour, num: f64) -> Colour {
         (colour.2 as f64 * num) as u8,
     )
 }
+
+/* Convert an rgb triple and alpha value to a CSS colour. */
+pub fn rgba_to_colour(rgb: Colour, alpha: f64) -> String {
+    format!(
+        "rgba({},{},{},{})",
+        rgb.0,
+        rgb.1,
+        rgb.2,
+        alpha.clamp(0., 1.)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn palettes_are_found_by_name() {
+        for palette in [
+            Palette::Random,
+            Palette::Gold,
+            Palette::Pastel,
+            Palette::Neon,
+            Palette::Dutch,
+        ] {
+            assert_eq!(Palette::from_name(palette.name()), Some(palette));
+        }
+
+        assert_eq!(Palette::from_name("plaid"), None);
+    }
+
+    #[test]
+    fn palettes_pick_their_own_colours() {
+        let mut random = Random::new();
+        for _ in 0..100 {
+            assert!(GOLD.contains(&Palette::Gold.random_colour(&mut random)));
+            assert!(DUTCH.contains(&Palette::Dutch.random_colour(&mut random)));
+
+            /* Pastel colours are at least as bright as the white in them. */
+            let (r, g, b) = Palette::Pastel.random_colour(&mut random);
+            assert!(r >= 150 && g >= 150 && b >= 150);
+        }
+    }
+
+    #[test]
+    fn adding_colours_saturates() {
+        assert_eq!(colour_add(&(100, 200, 0), &(100, 100, 0)), (200, 255, 0));
+        assert_eq!(colour_mul(&(100, 200, 255), 0.5), (50, 100, 127));
+    }
+
+    proptest! {
+        #[test]
+        fn mixing_colours_stays_in_range(
+            c1 in any::<(u8, u8, u8)>(),
+            c2 in any::<(u8, u8, u8)>(),
+            num in 0. ..=1f64,
+        ) {
+            let sum = colour_add(&c1, &c2);
+            prop_assert!(sum.0 >= c1.0.max(c2.0) && sum.1 >= c1.1.max(c2.1) && sum.2 >= c1.2.max(c2.2));
+
+            let product = colour_mul(&c1, num);
+            prop_assert!(product.0 <= c1.0 && product.1 <= c1.1 && product.2 <= c1.2);
+        }
+    }
+}
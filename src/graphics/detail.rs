@@ -0,0 +1,49 @@
+use crate::config::Settings;
+
+/* How much of the configured detail is drawn. The show lowers the detail on
+ * slow devices to keep a steady frame rate. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Detail {
+    /* The fraction of the particles in an explosion which is used. */
+    pub particles: f64,
+
+    /* The fraction of the fireworks which fit on the canvas. */
+    pub fireworks: f64,
+
+    /* The fraction of the trail length which is drawn. */
+    pub trails: f64,
+
+    pub glow: bool,
+
+    /* Whether bursts light up the sky. */
+    pub sky_flash: bool,
+}
+
+impl Detail {
+    /* Draw everything as configured. */
+    pub const FULL: Detail = Detail {
+        particles: 1.,
+        fireworks: 1.,
+        trails: 1.,
+        glow: true,
+        sky_flash: true,
+    };
+
+    /* Get the settings which are used at this level of detail. */
+    pub fn apply(&self, mut settings: Settings) -> Settings {
+        settings.particle_count =
+            ((settings.particle_count as f64 * self.particles).round() as u32).max(1);
+        settings.firework_spacing /= self.fireworks;
+        settings.trail_length *= self.trails;
+        settings.glow &= self.glow;
+        settings.sky_flash &= self.sky_flash;
+
+        settings
+    }
+}
+
+impl Default for Detail {
+    fn default() -> Self {
+        Detail::FULL
+    }
+}
@@ -1,15 +1,17 @@
-use colour::random_colour;
-use js_sys::Math;
-use web_sys::CanvasRenderingContext2d;
+use serde::{Deserialize, Serialize};
 
 use super::super::colour;
 use super::super::colour::Colour;
-use super::super::sim::{Particle, TwoVec};
+use super::super::random::Random;
+use super::super::render::Sprite;
+use super::super::sim::{Fields, Particle};
+use crate::config::Settings;
 
-use super::{vel_min_max, Firework, Rocket, GRAVITY, PARTICLE_COUNT, PARTICLE_LIFETIME};
+use super::{draw_spark, move_sparks, new_rocket, spark, Firework, FireworkState, Rocket};
 
 /* This struct represents firework of which the explosion gradually changes
  * colour over its lifetime. */
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ColourShiftFirework {
     rocket: Particle,
     exploded: bool,
@@ -29,84 +31,102 @@ impl Rocket for ColourShiftFirework {
         &self.rocket
     }
 
+    fn particles_mut(&mut self) -> &mut [Particle] {
+        &mut self.particles
+    }
+
     fn exploded(&self) -> bool {
         self.exploded
     }
 
     /* Dictate the explosion behaviour. */
-    fn explode(&mut self) -> () {
+    fn explode(&mut self, settings: &Settings, random: &mut Random) {
         self.exploded = true;
 
-        let radius = 1.5 + Math::random() * 1.5;
+        let radius = 1.5 + random.uniform() * 1.5;
 
         /* Create the explosion. */
-        for _ in 0..PARTICLE_COUNT {
-            let mut particle =
-                Particle::random_at(self.rocket.pos().clone(), radius + Math::random() * 0.5);
-            particle.set_vel(particle.vel() + self.rocket.vel());
-            self.particles.push(particle);
+        for _ in 0..settings.particles() {
+            let speed = radius + random.uniform() * 0.5;
+            self.particles.push(spark(
+                settings,
+                random,
+                &self.rocket,
+                *self.rocket.pos(),
+                speed,
+            ));
         }
     }
 
     /* Simulate the explosion for one step. */
-    fn sim_explosion(&mut self, width: u32, height: u32) -> () {
-        self.particles.iter_mut().for_each(|particle| {
-            particle.apply_force(GRAVITY);
-            particle.step();
-        });
+    fn sim_explosion(
+        &mut self,
+        settings: &Settings,
+        random: &mut Random,
+        fields: &Fields,
+        width: u32,
+        height: u32,
+    ) {
+        move_sparks(settings, fields, &mut self.particles, width, height);
 
         self.lifetime -= 1;
 
         if self.lifetime == 0 {
-            self.reset(width, height);
+            self.reset(settings, random, width, height);
         }
     }
 
     /* Draw the explosion on a given canvas. */
-    fn draw_explosion(&self, context: &CanvasRenderingContext2d) -> () {
+    fn draw_explosion(&self, settings: &Settings, sprites: &mut Vec<Sprite>) {
+        let lifetime_frac = (self.lifetime as f64) / (settings.particle_lifetime as f64);
+        let alpha = lifetime_frac.powi(2);
+
+        let colour = self.explosion_colour(settings);
+
         for particle in &self.particles {
-            let lifetime_frac = (self.lifetime as f64) / (PARTICLE_LIFETIME as f64);
-            let colour_shift = 1. - lifetime_frac.powi(6);
-            let alpha = lifetime_frac.powi(2);
-
-            particle.draw_rgba(
-                context,
-                /* Mix the two colours together in the right amount. */
-                colour::colour_add(
-                    &colour::colour_mul(&self.first_colour, colour_shift),
-                    &colour::colour_mul(&self.second_colour, 1. - colour_shift),
-                ),
-                alpha,
-                2.4,
-            );
+            draw_spark(settings, sprites, particle, colour, alpha, 2.4);
         }
     }
 
     /* Reset the explosion. */
-    fn reset_explosion(&mut self) -> () {
+    fn reset_explosion(&mut self, settings: &Settings, random: &mut Random) {
         self.exploded = false;
         self.particles.clear();
-        self.first_colour = random_colour();
-        self.second_colour = random_colour();
-        self.lifetime = PARTICLE_LIFETIME;
+        self.first_colour = settings.palette.random_colour(random);
+        self.second_colour = settings.palette.random_colour(random);
+        self.lifetime = settings.particle_lifetime;
+    }
+
+    /* Mix the two colours together in the right amount. */
+    fn explosion_colour(&self, settings: &Settings) -> Colour {
+        let lifetime_frac = (self.lifetime as f64) / (settings.particle_lifetime as f64);
+        let colour_shift = 1. - lifetime_frac.powi(6);
+
+        colour::colour_add(
+            &colour::colour_mul(&self.first_colour, colour_shift),
+            &colour::colour_mul(&self.second_colour, 1. - colour_shift),
+        )
+    }
+
+    fn lifetime(&self) -> u32 {
+        self.lifetime
+    }
+
+    fn state(&self) -> FireworkState {
+        FireworkState::ColourShift(self.clone())
     }
 }
 
 impl ColourShiftFirework {
     /* Create new firework at random position on the bottom, with random colour. */
-    pub fn new(width: u32, height: u32) -> Self {
-        let (vel_min, vel_max) = vel_min_max(height);
-
+    pub fn new(settings: &Settings, random: &mut Random, width: u32, height: u32) -> Self {
         Self {
-            rocket: Particle::new(
-                TwoVec::new(Math::random() * width as f64, height as f64),
-                TwoVec::new(0., vel_min + (vel_max - vel_min) * Math::random()),
-            ),
+            rocket: new_rocket(settings, random, width, height),
             exploded: false,
             particles: Vec::new(),
-            first_colour: random_colour(),
-            second_colour: random_colour(),
-            lifetime: PARTICLE_LIFETIME,
+            first_colour: settings.palette.random_colour(random),
+            second_colour: settings.palette.random_colour(random),
+            lifetime: settings.particle_lifetime,
         }
     }
 }
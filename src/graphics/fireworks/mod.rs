@@ -2,29 +2,87 @@ mod colour_shift;
 mod sparkle;
 mod standard;
 
-use js_sys::Math;
-use web_sys::CanvasRenderingContext2d;
+use serde::{Deserialize, Serialize};
 
 use super::colour;
-use super::sim::{Particle, TwoVec};
+use super::colour::Colour;
+use super::random::Random;
+use super::render::Sprite;
+use super::sim::{Bounds, Fields, Particle, ThreeVec, TwoVec};
+use crate::config::Settings;
 
-const PARTICLE_COUNT: u32 = 20;
+/* The size of the glow around a spark, relative to the spark. */
+const GLOW_SIZE: f64 = 3.;
 
-const PARTICLE_LIFETIME: u32 = 70;
+const GLOW_ALPHA: f64 = 0.15;
 
-const ROCKET_EXPLODE_SPEED: f64 = -2.;
+const TRAIL_ALPHA: f64 = 0.4;
 
-pub const GRAVITY: TwoVec = TwoVec::new(0., 0.07);
+/* How far beside the canvas sparks are still simulated, so their glow and
+ * trails do not disappear at the edge. */
+const CULL_MARGIN: f64 = 30.;
+
+/* The types of firework there are. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FireworkKind {
+    Standard,
+    ColourShift,
+    Sparkle,
+}
+
+/* The light given off by an exploding firework. */
+pub struct Burst {
+    pub pos: ThreeVec,
+    pub colour: Colour,
+    pub intensity: f64,
+}
 
 pub trait Firework {
     /* Reset the firework. */
-    fn reset(&mut self, width: u32, height: u32);
+    fn reset(&mut self, settings: &Settings, random: &mut Random, width: u32, height: u32);
+
+    /* Launch the rocket straight up from the given position on the bottom
+     * of the canvas, with the given vertical velocity. In 3D it goes up at
+     * a random depth. */
+    fn launch(
+        &mut self,
+        settings: &Settings,
+        random: &mut Random,
+        x: f64,
+        width: u32,
+        height: u32,
+        vel: f64,
+    );
+
+    /* Simulate one step of the firework, with its sparks pushed around by
+     * the force fields. */
+    fn step(
+        &mut self,
+        settings: &Settings,
+        random: &mut Random,
+        fields: &Fields,
+        width: u32,
+        height: u32,
+    );
 
-    /* Simulate one step of the firework. */
-    fn step(&mut self, width: u32, height: u32);
+    /* Draw the firework as sprites. */
+    fn draw(&self, settings: &Settings, sprites: &mut Vec<Sprite>);
 
-    /* Draw the firework. */
-    fn draw(&self, context: &CanvasRenderingContext2d);
+    /* Get the burst of the firework if it has exploded. */
+    fn burst(&self, settings: &Settings) -> Option<Burst>;
+
+    /* The position of the rocket, or the centre of its explosion, in
+     * space. */
+    fn pos(&self) -> ThreeVec;
+
+    /* Whether the explosion crackles while it burns. */
+    fn crackles(&self) -> bool;
+
+    /* Stretch the firework along with the canvas. */
+    fn rescale(&mut self, settings: &Settings, x_scale: f64, y_scale: f64);
+
+    /* Get a copy of the complete state of the firework. */
+    fn state(&self) -> FireworkState;
 }
 
 /* A rocket is a simple firework which will shoot straight up and explode in
@@ -34,14 +92,37 @@ pub trait Rocket {
     fn rocket_mut(&mut self) -> &mut Particle;
     fn rocket(&self) -> &Particle;
 
+    /* The particles of the explosion. */
+    fn particles_mut(&mut self) -> &mut [Particle];
+
     fn exploded(&self) -> bool;
-    fn explode(&mut self) -> ();
+    fn explode(&mut self, settings: &Settings, random: &mut Random);
 
-    fn sim_explosion(&mut self, width: u32, height: u32) -> ();
+    fn sim_explosion(
+        &mut self,
+        settings: &Settings,
+        random: &mut Random,
+        fields: &Fields,
+        width: u32,
+        height: u32,
+    );
 
-    fn draw_explosion(&self, context: &CanvasRenderingContext2d) -> ();
+    fn draw_explosion(&self, settings: &Settings, sprites: &mut Vec<Sprite>);
 
-    fn reset_explosion(&mut self) -> ();
+    fn reset_explosion(&mut self, settings: &Settings, random: &mut Random);
+
+    /* The colour the explosion currently has. */
+    fn explosion_colour(&self, settings: &Settings) -> Colour;
+
+    /* The remaining lifetime of the explosion. */
+    fn lifetime(&self) -> u32;
+
+    fn state(&self) -> FireworkState;
+
+    /* Most explosions burn quietly. */
+    fn crackles(&self) -> bool {
+        false
+    }
 }
 
 /* Implement the firework trait for a struct with the Rocket trait.. */
@@ -50,42 +131,240 @@ where
     T: Rocket,
 {
     /* Reset the rocket in its entirety. */
-    fn reset(&mut self, width: u32, height: u32) {
-        let (vel_min, vel_max) = vel_min_max(height);
+    fn reset(&mut self, settings: &Settings, random: &mut Random, width: u32, height: u32) {
+        let x = random.uniform() * width as f64;
+        let vel = random_vel(random, height);
+        self.launch(settings, random, x, width, height, vel);
+    }
 
-        self.rocket_mut()
-            .set_pos(TwoVec::new(Math::random() * width as f64, height as f64));
-        self.rocket_mut().set_vel(TwoVec::new(
-            0.,
-            vel_min + (vel_max - vel_min) * Math::random(),
-        ));
+    fn launch(
+        &mut self,
+        settings: &Settings,
+        random: &mut Random,
+        x: f64,
+        width: u32,
+        height: u32,
+        vel: f64,
+    ) {
+        self.reset_explosion(settings, random);
 
-        self.reset_explosion();
+        let depth = launch_depth(settings, random, width);
+        self.rocket_mut()
+            .set_pos(ThreeVec::from_plane(TwoVec::new(x, height as f64), depth));
+        self.rocket_mut().set_vel(TwoVec::new(0., vel));
     }
 
     /* Simulate one step of the rocket. */
-    fn step(&mut self, width: u32, height: u32) -> () {
+    fn step(
+        &mut self,
+        settings: &Settings,
+        random: &mut Random,
+        fields: &Fields,
+        width: u32,
+        height: u32,
+    ) {
         if !self.exploded() {
-            self.rocket_mut().apply_force(GRAVITY);
-            self.rocket_mut().step();
+            fall(settings, self.rocket_mut());
 
             /* If the rocket is at the top of its arc, explode it. */
-            if self.rocket().vel().y() > ROCKET_EXPLODE_SPEED {
-                self.explode();
+            if self.rocket().vel().y() > settings.rocket_explode_speed {
+                self.explode(settings, random);
             }
         } else {
-            self.sim_explosion(width, height);
+            self.sim_explosion(settings, random, fields, width, height);
         }
     }
 
     /* Draw the rocket or its explosion. */
-    fn draw(&self, context: &CanvasRenderingContext2d) -> () {
+    fn draw(&self, settings: &Settings, sprites: &mut Vec<Sprite>) {
         if !self.exploded() {
-            self.rocket().draw(context, colour::ORANGE, 2.3);
+            sprites.push(self.rocket().sprite(colour::ORANGE, 1., 2.3));
         } else {
-            self.draw_explosion(context);
+            self.draw_explosion(settings, sprites);
+        }
+    }
+
+    /* The explosion is centered where the rocket stopped, and fades along
+     * with its particles. The lifetime can be lowered while the explosion
+     * burns, which should not make it any brighter. */
+    fn burst(&self, settings: &Settings) -> Option<Burst> {
+        if !self.exploded() {
+            return None;
         }
+
+        Some(Burst {
+            pos: *self.rocket().pos(),
+            colour: self.explosion_colour(settings),
+            intensity: (self.lifetime() as f64 / settings.particle_lifetime as f64).min(1.),
+        })
+    }
+
+    fn pos(&self) -> ThreeVec {
+        *self.rocket().pos()
     }
+
+    fn crackles(&self) -> bool {
+        Rocket::crackles(self)
+    }
+
+    /* A rising rocket keeps climbing the same part of the canvas, just like
+     * launch velocities depend on the height of the canvas. */
+    fn rescale(&mut self, settings: &Settings, x_scale: f64, y_scale: f64) {
+        let rocket = self.rocket_mut();
+        let pos = *rocket.pos();
+        let vel = *rocket.vel();
+
+        rocket.set_pos(ThreeVec::new(
+            pos.x() * x_scale,
+            pos.y() * y_scale,
+            pos.z() * x_scale,
+        ));
+
+        if !self.exploded() {
+            self.rocket_mut()
+                .set_vel(TwoVec::new(0., rise_vel(settings, vel.y(), y_scale)));
+        }
+
+        for particle in self.particles_mut() {
+            particle.rescale(x_scale, y_scale);
+        }
+    }
+
+    fn state(&self) -> FireworkState {
+        Rocket::state(self)
+    }
+}
+
+/* The complete state of a firework of any type, to save and restore it. */
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FireworkState {
+    Standard(StandardFirework),
+    ColourShift(ColourShiftFirework),
+    Sparkle(SparkleFirework),
+}
+
+impl FireworkState {
+    /* Turn the state back into a firework. */
+    pub fn restore(self) -> Box<dyn Firework> {
+        match self {
+            FireworkState::Standard(firework) => Box::new(firework),
+            FireworkState::ColourShift(firework) => Box::new(firework),
+            FireworkState::Sparkle(firework) => Box::new(firework),
+        }
+    }
+}
+
+/* Pick the depth a rocket is launched at. In 3D, rockets are launched all
+ * over a square as deep as the canvas is wide, otherwise from the canvas. */
+fn launch_depth(settings: &Settings, random: &mut Random, width: u32) -> f64 {
+    if settings.three_d {
+        (random.uniform() - 0.5) * width as f64
+    } else {
+        0.
+    }
+}
+
+/* Create a rocket at a random position on the bottom, with a random launch
+ * velocity. */
+fn new_rocket(settings: &Settings, random: &mut Random, width: u32, height: u32) -> Particle {
+    let (vel_min, vel_max) = vel_min_max(height);
+    let pos = TwoVec::new(random.uniform() * width as f64, height as f64);
+    let depth = launch_depth(settings, random, width);
+
+    Particle::in_space(
+        ThreeVec::from_plane(pos, depth),
+        ThreeVec::new(0., vel_min + (vel_max - vel_min) * random.uniform(), 0.),
+    )
+}
+
+/* Create a spark of an explosion at the given position, flying away from it
+ * with the given speed and the velocity of the rocket. In 3D the sparks fly
+ * out in all directions to make a sphere, otherwise along the canvas. */
+fn spark(
+    settings: &Settings,
+    random: &mut Random,
+    rocket: &Particle,
+    pos: ThreeVec,
+    speed: f64,
+) -> Particle {
+    let mut spark = if settings.three_d {
+        Particle::random_in_space(random, pos, speed)
+    } else {
+        Particle::random_at(random, pos, speed)
+    };
+    spark.set_vel(spark.vel() + rocket.vel());
+
+    spark
+}
+
+/* Move a particle one step under gravity, with the integrator of the show. */
+fn fall(settings: &Settings, particle: &mut Particle) {
+    particle.apply_force(TwoVec::new(0., settings.gravity));
+    particle.step(settings.integrator.integrator(), 1.);
+}
+
+/* Move the sparks of an explosion one step. They are pushed by the force
+ * fields, bounce off the ground and walls, and are removed once they leave
+ * the canvas for good. */
+fn move_sparks(
+    settings: &Settings,
+    fields: &Fields,
+    sparks: &mut Vec<Particle>,
+    width: u32,
+    height: u32,
+) {
+    let bounds = Bounds {
+        width: width as f64,
+        height: height as f64,
+        walls: settings.walls,
+        restitution: settings.restitution,
+        friction: settings.friction,
+    };
+
+    for spark in sparks.iter_mut() {
+        spark.apply_force(fields.force(&spark.pos().xy()));
+        fall(settings, spark);
+        bounds.collide(spark);
+    }
+
+    /* In 3D the camera sees beside the canvas as well, so there the sparks
+     * last as long as the explosion. */
+    if !settings.three_d {
+        sparks.retain(|spark| bounds.contains(spark, CULL_MARGIN));
+    }
+}
+
+/* Draw a glow around a spark if it is enabled. */
+fn draw_glow(
+    settings: &Settings,
+    sprites: &mut Vec<Sprite>,
+    particle: &Particle,
+    colour: Colour,
+    alpha: f64,
+    radius: f64,
+) {
+    if settings.glow {
+        sprites.push(particle.sprite(colour, alpha * GLOW_ALPHA, radius * GLOW_SIZE));
+    }
+}
+
+/* Draw a moving spark of an explosion, with its glow and trail. */
+fn draw_spark(
+    settings: &Settings,
+    sprites: &mut Vec<Sprite>,
+    particle: &Particle,
+    colour: Colour,
+    alpha: f64,
+    radius: f64,
+) {
+    draw_glow(settings, sprites, particle, colour, alpha, radius);
+
+    if settings.trail_length > 0. {
+        sprites.push(particle.trail(colour, alpha * TRAIL_ALPHA, settings.trail_length, radius));
+    }
+
+    sprites.push(particle.sprite(colour, alpha, radius));
 }
 
 /* Calculate the min and max starting velocity based on screen height. */
@@ -94,6 +373,401 @@ fn vel_min_max(height: u32) -> (f64, f64) {
     (height_root / -5.5, height_root / -3.)
 }
 
+/* Pick a random launch velocity based on screen height. */
+pub fn random_vel(random: &mut Random, height: u32) -> f64 {
+    let (vel_min, vel_max) = vel_min_max(height);
+
+    vel_min + (vel_max - vel_min) * random.uniform()
+}
+
+/* Calculate the vertical velocity with which a rocket rises the given factor
+ * as far before exploding as it would with the given velocity. */
+fn rise_vel(settings: &Settings, vel: f64, factor: f64) -> f64 {
+    let explode_speed = settings.rocket_explode_speed;
+    if vel >= explode_speed {
+        return vel;
+    }
+
+    /* The distance left to rise is proportional to the difference of the
+     * squared velocities. */
+    -(explode_speed.powi(2) + (vel.powi(2) - explode_speed.powi(2)) * factor).sqrt()
+}
+
+/* Calculate the amount of steps a rocket launched with the given vertical
+ * velocity flies before exploding. */
+pub fn flight_steps(settings: &Settings, vel: f64) -> u64 {
+    if vel > settings.rocket_explode_speed {
+        return 1;
+    }
+
+    ((settings.rocket_explode_speed - vel) / settings.gravity) as u64 + 1
+}
+
+/* Calculate the vertical velocity a rocket needs to fly for the given amount
+ * of steps before exploding. This is the inverse of flight_steps. */
+pub fn launch_vel(settings: &Settings, steps: u64) -> f64 {
+    settings.rocket_explode_speed - (steps as f64 - 0.5) * settings.gravity
+}
+
+/* Calculate the shortest and longest flight in steps based on screen height. */
+pub fn flight_range(settings: &Settings, height: u32) -> (u64, u64) {
+    let (vel_min, vel_max) = vel_min_max(height);
+
+    (
+        flight_steps(settings, vel_min),
+        flight_steps(settings, vel_max),
+    )
+}
+
 pub use colour_shift::ColourShiftFirework;
 pub use sparkle::SparkleFirework;
 pub use standard::StandardFirework;
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::super::sim::{Field, FieldKind, IntegratorKind};
+    use super::*;
+
+    const WIDTH: u32 = 800;
+    const HEIGHT: u32 = 600;
+
+    /* Launch a rocket which explodes after the given amount of steps, and
+     * check that it explodes exactly then. */
+    fn check_flight(
+        firework: &mut impl Rocket,
+        settings: &Settings,
+        random: &mut Random,
+        steps: u64,
+    ) {
+        firework.launch(
+            settings,
+            random,
+            100.,
+            WIDTH,
+            HEIGHT,
+            launch_vel(settings, steps),
+        );
+
+        for _ in 1..steps {
+            firework.step(settings, random, &Fields::default(), WIDTH, HEIGHT);
+            assert!(!firework.exploded());
+            assert!(firework.burst(settings).is_none());
+        }
+
+        firework.step(settings, random, &Fields::default(), WIDTH, HEIGHT);
+        assert!(firework.exploded());
+        assert!(firework.burst(settings).is_some());
+    }
+
+    /* Let an explosion burn out, and check that the firework starts anew. */
+    fn check_lifetime(
+        firework: &mut impl Rocket,
+        settings: &Settings,
+        random: &mut Random,
+        particles: usize,
+    ) {
+        check_flight(firework, settings, random, 80);
+        assert_eq!(firework.particles_mut().len(), particles);
+
+        for _ in 1..settings.particle_lifetime {
+            firework.step(settings, random, &Fields::default(), WIDTH, HEIGHT);
+            assert!(firework.exploded());
+        }
+
+        let burst = firework.burst(settings).unwrap();
+        assert!(burst.intensity > 0. && burst.intensity < 0.1);
+
+        firework.step(settings, random, &Fields::default(), WIDTH, HEIGHT);
+        assert!(!firework.exploded());
+        assert!(firework.particles_mut().is_empty());
+        assert_eq!(firework.rocket().pos().y(), HEIGHT as f64);
+        assert!(firework.rocket().vel().y() < settings.rocket_explode_speed);
+    }
+
+    #[test]
+    fn standard_fireworks_explode_and_start_over() {
+        let mut random = Random::new();
+        let settings = Settings::default();
+        let mut firework = StandardFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+
+        check_lifetime(
+            &mut firework,
+            &settings,
+            &mut random,
+            settings.particles() as usize,
+        );
+    }
+
+    #[test]
+    fn colour_shift_fireworks_explode_and_start_over() {
+        let mut random = Random::new();
+        let settings = Settings::default();
+        let mut firework = ColourShiftFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+
+        check_lifetime(
+            &mut firework,
+            &settings,
+            &mut random,
+            settings.particles() as usize,
+        );
+    }
+
+    #[test]
+    fn sparkle_fireworks_explode_and_start_over() {
+        let mut random = Random::new();
+        let settings = Settings::default();
+        let mut firework = SparkleFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+
+        check_lifetime(
+            &mut firework,
+            &settings,
+            &mut random,
+            settings.particles() as usize * 10,
+        );
+    }
+
+    #[test]
+    fn bursts_are_never_brighter_than_full() {
+        let mut random = Random::new();
+        let settings = Settings::default();
+        let mut firework = StandardFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+        check_flight(&mut firework, &settings, &mut random, 60);
+
+        let shorter = Settings {
+            particle_lifetime: settings.particle_lifetime / 4,
+            ..settings
+        };
+        assert_eq!(firework.burst(&shorter).unwrap().intensity, 1.);
+    }
+
+    #[test]
+    fn sparks_land_or_leave() {
+        let mut random = Random::new();
+        let settings = Settings {
+            particle_lifetime: 1000,
+            ..Settings::default()
+        };
+        let mut firework = StandardFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+        check_flight(&mut firework, &settings, &mut random, 60);
+        let sparks = firework.particles_mut().len();
+
+        for _ in 0..900 {
+            firework.step(&settings, &mut random, &Fields::default(), WIDTH, HEIGHT);
+        }
+
+        let particles = firework.particles_mut();
+        assert!(particles.len() < sparks);
+        for particle in particles {
+            assert!((particle.pos().y() - HEIGHT as f64).abs() < 1.);
+            assert!(particle.vel().length() < 0.5);
+        }
+    }
+
+    #[test]
+    fn fields_push_the_sparks() {
+        let mut random = Random::new();
+        let settings = Settings::default();
+        let mut fields = Fields::default();
+        fields.set_fields(vec![Field::pointer(FieldKind::Repulsor)]);
+
+        let mut firework = SparkleFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+        check_flight(&mut firework, &settings, &mut random, 60);
+        let before: Vec<TwoVec> = firework
+            .particles_mut()
+            .iter()
+            .map(|p| p.pos().xy())
+            .collect();
+
+        /* Without the pointer, the sparkles hang still. */
+        firework.step(&settings, &mut random, &fields, WIDTH, HEIGHT);
+        let still: Vec<TwoVec> = firework
+            .particles_mut()
+            .iter()
+            .map(|p| p.pos().xy())
+            .collect();
+        assert_eq!(before, still);
+
+        let centre = Firework::pos(&firework).xy();
+        fields.set_pointer(Some(centre));
+        firework.step(&settings, &mut random, &fields, WIDTH, HEIGHT);
+
+        for (particle, before) in firework.particles_mut().iter().zip(&before) {
+            let distance = |pos: &TwoVec| (pos - centre).length();
+            assert!(distance(&particle.pos().xy()) >= distance(before));
+        }
+    }
+
+    #[test]
+    fn sparks_beside_the_canvas_are_only_removed_when_flat() {
+        let beside = || vec![Particle::new(TwoVec::new(-100., 100.), TwoVec::zero())];
+        let flat = Settings::default();
+        let three_d = Settings {
+            three_d: true,
+            ..Settings::default()
+        };
+
+        let mut sparks = beside();
+        move_sparks(&flat, &Fields::default(), &mut sparks, WIDTH, HEIGHT);
+        assert!(sparks.is_empty());
+
+        /* The orbiting camera may still see them. */
+        let mut sparks = beside();
+        move_sparks(&three_d, &Fields::default(), &mut sparks, WIDTH, HEIGHT);
+        assert_eq!(sparks.len(), 1);
+    }
+
+    #[test]
+    fn sparkles_hang_still_with_every_integrator() {
+        for integrator in [
+            IntegratorKind::SemiImplicitEuler,
+            IntegratorKind::VelocityVerlet,
+            IntegratorKind::RungeKutta4,
+        ] {
+            let mut random = Random::seeded(7);
+            let settings = Settings {
+                integrator,
+                ..Settings::default()
+            };
+
+            let mut firework = SparkleFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+            check_flight(&mut firework, &settings, &mut random, 60);
+            let before: Vec<ThreeVec> = firework.particles_mut().iter().map(|p| *p.pos()).collect();
+
+            for _ in 0..10 {
+                firework.step(&settings, &mut random, &Fields::default(), WIDTH, HEIGHT);
+            }
+            assert!(firework
+                .particles_mut()
+                .iter()
+                .zip(&before)
+                .all(|(particle, before)| particle.pos() == before));
+        }
+    }
+
+    #[test]
+    fn explosions_are_spheres_in_3d() {
+        let mut random = Random::new();
+        let settings = Settings {
+            three_d: true,
+            ..Settings::default()
+        };
+        let mut flat = StandardFirework::new(&Settings::default(), &mut random, WIDTH, HEIGHT);
+        let mut sphere = StandardFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+        check_flight(&mut flat, &Settings::default(), &mut random, 60);
+        check_flight(&mut sphere, &settings, &mut random, 60);
+
+        let rocket_vel = *sphere.rocket().vel();
+        let depths: Vec<f64> = sphere
+            .particles_mut()
+            .iter()
+            .map(|particle| (particle.vel() - rocket_vel).z())
+            .collect();
+        assert!(depths.iter().any(|&depth| depth > 0.5));
+        assert!(depths.iter().any(|&depth| depth < -0.5));
+        assert!(flat
+            .particles_mut()
+            .iter()
+            .all(|particle| particle.vel().z() == 0.));
+
+        /* New rockets are launched all over the depth of the stage, also
+         * those launched on a beat or by a click. */
+        for relaunch in [true, false] {
+            let depths: Vec<f64> = (0..50)
+                .map(|_| {
+                    if relaunch {
+                        sphere.reset(&settings, &mut random, WIDTH, HEIGHT);
+                    } else {
+                        let vel = launch_vel(&settings, 60);
+                        sphere.launch(&settings, &mut random, 400., WIDTH, HEIGHT, vel);
+                    }
+                    sphere.rocket().pos().z()
+                })
+                .collect();
+            assert!(depths.iter().all(|depth| depth.abs() <= WIDTH as f64 / 2.));
+            assert!(depths.iter().any(|&depth| depth != 0.));
+        }
+    }
+
+    #[test]
+    fn launch_velocities_stay_in_range() {
+        let mut random = Random::new();
+        let settings = Settings::default();
+        let (shortest, longest) = flight_range(&settings, HEIGHT);
+        assert!(shortest < longest);
+
+        for _ in 0..100 {
+            let steps = flight_steps(&settings, random_vel(&mut random, HEIGHT));
+            assert!((shortest..=longest).contains(&steps));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn rockets_explode_after_their_flight(steps in 1..300u64, gravity in 0.01..0.5f64) {
+            let mut random = Random::new();
+            let settings = Settings { gravity, ..Settings::default() };
+            let mut firework = StandardFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+
+            prop_assert_eq!(flight_steps(&settings, launch_vel(&settings, steps)), steps);
+            check_flight(&mut firework, &settings, &mut random, steps);
+        }
+
+        #[test]
+        fn rescaled_rockets_rise_in_proportion(vel in -30. ..-2.01f64, factor in 0.1..10f64) {
+            let settings = Settings::default();
+            let explode_speed = settings.rocket_explode_speed;
+            let rise = |vel: f64| vel.powi(2) - explode_speed.powi(2);
+
+            let scaled = rise_vel(&settings, vel, factor);
+            prop_assert!(scaled < explode_speed);
+            prop_assert!((rise(scaled) - rise(vel) * factor).abs() < 1e-6 * rise(vel) * factor);
+
+            /* Scaling back gives the original velocity. */
+            prop_assert!((rise_vel(&settings, scaled, 1. / factor) - vel).abs() < 1e-9);
+        }
+
+        #[test]
+        fn rescaling_fireworks_is_reversible(
+            steps in 0..200u32,
+            x_scale in 0.1..10f64,
+            y_scale in 0.1..10f64,
+        ) {
+            let settings = Settings::default();
+            let mut random = Random::new();
+            let mut firework = ColourShiftFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+            for _ in 0..steps {
+                firework.step(&settings, &mut random, &Fields::default(), WIDTH, HEIGHT);
+            }
+            let before = serde_json::to_value(Firework::state(&firework)).unwrap();
+
+            firework.rescale(&settings, x_scale, y_scale);
+            firework.rescale(&settings, 1. / x_scale, 1. / y_scale);
+            let after = serde_json::to_value(Firework::state(&firework)).unwrap();
+
+            prop_assert!(close(&before, &after), "{} became {}", before, after);
+        }
+    }
+
+    /* Compare two JSON values, allowing for rounding in numbers. */
+    fn close(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+        use serde_json::Value;
+
+        match (a, b) {
+            (Value::Number(a), Value::Number(b)) => {
+                let (a, b) = (a.as_f64().unwrap(), b.as_f64().unwrap());
+                (a - b).abs() <= 1e-6 * a.abs().max(1.)
+            }
+            (Value::Array(a), Value::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| close(a, b))
+            }
+            (Value::Object(a), Value::Object(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(key, a)| b.get(key).is_some_and(|b| close(a, b)))
+            }
+            (a, b) => a == b,
+        }
+    }
+}
@@ -1,19 +1,21 @@
-use js_sys::Math;
-use web_sys::CanvasRenderingContext2d;
+use serde::{Deserialize, Serialize};
 
 use super::super::colour::Colour;
-use super::super::sim::{Particle, TwoVec};
-use super::{Firework, Rocket, PARTICLE_COUNT, PARTICLE_LIFETIME};
-
-use super::vel_min_max;
+use super::super::random::Random;
+use super::super::render::Sprite;
+use super::super::sim::{Fields, Particle, ThreeVec, TwoVec};
+use super::{draw_glow, new_rocket, Firework, FireworkState, Rocket};
+use crate::config::Settings;
 
 const SPARKLE_COLOUR: Colour = (255, 238, 150);
 
 /* This struct represents a sparkling firework. */
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SparkleFirework {
     rocket: Particle,
     exploded: bool,
     particles: Vec<Particle>,
+    #[serde(with = "crate::graphics::float::vec")]
     phases: Vec<f64>,
     lifetime: u32,
 }
@@ -28,77 +30,108 @@ impl Rocket for SparkleFirework {
         &self.rocket
     }
 
+    fn particles_mut(&mut self) -> &mut [Particle] {
+        &mut self.particles
+    }
+
     fn exploded(&self) -> bool {
         self.exploded
     }
 
     /* Explode the firework. */
-    fn explode(&mut self) -> () {
+    fn explode(&mut self, settings: &Settings, random: &mut Random) {
         self.exploded = true;
 
-        let radius = 1.5 + Math::random() * 1.5;
-
-        /* Create the explosion. */
-        for _ in 0..(PARTICLE_COUNT * 10) {
-            let mut particle = Particle::random_at(
-                self.rocket.pos() + &TwoVec::random(70. * Math::random().powf(0.7)),
-                radius + Math::random() * 0.5,
-            );
-            particle.set_vel(particle.vel() + self.rocket.vel());
-            self.particles.push(particle);
+        /* Create the explosion. The sparkles hang still where they appear,
+         * with every integrator. */
+        for _ in 0..(settings.particles() * 10) {
+            /* The sparkles fill a disc, or a ball in 3D. */
+            let spread = 70. * random.uniform().powf(0.7);
+            let offset = if settings.three_d {
+                ThreeVec::random(random, spread)
+            } else {
+                TwoVec::random(random, spread).into()
+            };
+
+            self.particles.push(Particle::in_space(
+                self.rocket.pos() + offset,
+                ThreeVec::zero(),
+            ));
         }
 
-        self.phases = (0..(PARTICLE_COUNT * 10))
-            .into_iter()
-            .map(|_| Math::random() * 10.)
+        self.phases = (0..(settings.particles() * 10))
+            .map(|_| random.uniform() * 10.)
             .collect();
     }
 
     /* Simulate one step of the explosion. */
-    fn sim_explosion(&mut self, width: u32, height: u32) -> () {
+    fn sim_explosion(
+        &mut self,
+        settings: &Settings,
+        random: &mut Random,
+        fields: &Fields,
+        width: u32,
+        height: u32,
+    ) {
+        /* Drag takes away about all their speed every step, so the sparkles
+         * only move while a field pushes them. */
+        for particle in &mut self.particles {
+            particle.apply_force(fields.force(&particle.pos().xy()));
+            particle.integrate(settings.integrator.integrator(), 1., |_, vel| -vel);
+        }
+
         self.lifetime -= 1;
 
         if self.lifetime == 0 {
-            self.reset(width, height);
+            self.reset(settings, random, width, height);
         }
     }
 
-    /* Draw the explosion. */
-    fn draw_explosion(&self, context: &CanvasRenderingContext2d) -> () {
+    /* Draw the explosion. The sparkles hang still, so they have no trail. */
+    fn draw_explosion(&self, settings: &Settings, sprites: &mut Vec<Sprite>) {
         for (particle, phase) in self.particles.iter().zip(&self.phases) {
-            particle.draw_rgba(
-                context,
-                SPARKLE_COLOUR,
-                (self.lifetime as f64) / (PARTICLE_LIFETIME as f64)
-                    * ((self.lifetime as f64 / 5. + phase).cos() + 1.)
-                    / 2.,
-                1.5,
-            );
+            let alpha = (self.lifetime as f64) / (settings.particle_lifetime as f64)
+                * ((self.lifetime as f64 / 5. + phase).cos() + 1.)
+                / 2.;
+
+            draw_glow(settings, sprites, particle, SPARKLE_COLOUR, alpha, 1.5);
+            sprites.push(particle.sprite(SPARKLE_COLOUR, alpha, 1.5));
         }
     }
 
     /* Reset the explosion. */
-    fn reset_explosion(&mut self) -> () {
+    fn reset_explosion(&mut self, settings: &Settings, _random: &mut Random) {
         self.exploded = false;
         self.particles.clear();
-        self.lifetime = PARTICLE_LIFETIME;
+        self.lifetime = settings.particle_lifetime;
+    }
+
+    fn explosion_colour(&self, _settings: &Settings) -> Colour {
+        SPARKLE_COLOUR
+    }
+
+    fn lifetime(&self) -> u32 {
+        self.lifetime
+    }
+
+    fn state(&self) -> FireworkState {
+        FireworkState::Sparkle(self.clone())
+    }
+
+    fn crackles(&self) -> bool {
+        true
     }
 }
 
 impl SparkleFirework {
     /* Create new firework at random position on the bottom, with random colour. */
-    pub fn new(width: u32, height: u32) -> Self {
-        let (vel_min, vel_max) = vel_min_max(height);
-
+    pub fn new(settings: &Settings, random: &mut Random, width: u32, height: u32) -> Self {
         Self {
-            rocket: Particle::new(
-                TwoVec::new(Math::random() * width as f64, height as f64),
-                TwoVec::new(0., vel_min + (vel_max - vel_min) * Math::random()),
-            ),
+            rocket: new_rocket(settings, random, width, height),
             exploded: false,
             particles: Vec::new(),
             phases: Vec::new(),
-            lifetime: PARTICLE_LIFETIME,
+            lifetime: settings.particle_lifetime,
         }
     }
 }
@@ -1,15 +1,14 @@
-use colour::random_colour;
-use js_sys::Math;
-use web_sys::CanvasRenderingContext2d;
+use serde::{Deserialize, Serialize};
 
-use super::super::colour;
 use super::super::colour::Colour;
-use super::super::sim::{Particle, TwoVec};
-use super::{Firework, Rocket, GRAVITY, PARTICLE_COUNT, PARTICLE_LIFETIME};
-
-use super::vel_min_max;
+use super::super::random::Random;
+use super::super::render::Sprite;
+use super::super::sim::{Fields, Particle};
+use super::{draw_spark, move_sparks, new_rocket, spark, Firework, FireworkState, Rocket};
+use crate::config::Settings;
 
 /* This struct represents a plain firework with one colour.. */
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StandardFirework {
     rocket: Particle,
     exploded: bool,
@@ -28,74 +27,95 @@ impl Rocket for StandardFirework {
         &self.rocket
     }
 
+    fn particles_mut(&mut self) -> &mut [Particle] {
+        &mut self.particles
+    }
+
     fn exploded(&self) -> bool {
         self.exploded
     }
 
     /* Explode the firework. */
-    fn explode(&mut self) -> () {
+    fn explode(&mut self, settings: &Settings, random: &mut Random) {
         self.exploded = true;
 
-        let radius = 1.5 + Math::random() * 1.5;
+        let radius = 1.5 + random.uniform() * 1.5;
 
         /* Create the explosion. */
-        for _ in 0..PARTICLE_COUNT {
-            let mut particle =
-                Particle::random_at(self.rocket.pos().clone(), radius + Math::random() * 0.5);
-            particle.set_vel(particle.vel() + self.rocket.vel());
-            self.particles.push(particle);
+        for _ in 0..settings.particles() {
+            let speed = radius + random.uniform() * 0.5;
+            self.particles.push(spark(
+                settings,
+                random,
+                &self.rocket,
+                *self.rocket.pos(),
+                speed,
+            ));
         }
     }
 
     /* Simulate one step of the explosion. */
-    fn sim_explosion(&mut self, width: u32, height: u32) -> () {
-        self.particles.iter_mut().for_each(|particle| {
-            particle.apply_force(GRAVITY);
-            particle.step();
-        });
+    fn sim_explosion(
+        &mut self,
+        settings: &Settings,
+        random: &mut Random,
+        fields: &Fields,
+        width: u32,
+        height: u32,
+    ) {
+        move_sparks(settings, fields, &mut self.particles, width, height);
 
         self.lifetime -= 1;
 
         if self.lifetime == 0 {
-            self.reset(width, height);
+            self.reset(settings, random, width, height);
         }
     }
 
     /* Draw the explosion. */
-    fn draw_explosion(&self, context: &CanvasRenderingContext2d) -> () {
+    fn draw_explosion(&self, settings: &Settings, sprites: &mut Vec<Sprite>) {
         for particle in &self.particles {
-            particle.draw_rgba(
-                context,
+            draw_spark(
+                settings,
+                sprites,
+                particle,
                 self.colour,
-                (self.lifetime as f64) / (PARTICLE_LIFETIME as f64),
+                (self.lifetime as f64) / (settings.particle_lifetime as f64),
                 2.4,
             );
         }
     }
 
     /* Reset the explosion. */
-    fn reset_explosion(&mut self) -> () {
+    fn reset_explosion(&mut self, settings: &Settings, random: &mut Random) {
         self.exploded = false;
         self.particles.clear();
-        self.colour = colour::random_colour();
-        self.lifetime = PARTICLE_LIFETIME;
+        self.colour = settings.palette.random_colour(random);
+        self.lifetime = settings.particle_lifetime;
+    }
+
+    fn explosion_colour(&self, _settings: &Settings) -> Colour {
+        self.colour
+    }
+
+    fn lifetime(&self) -> u32 {
+        self.lifetime
+    }
+
+    fn state(&self) -> FireworkState {
+        FireworkState::Standard(self.clone())
     }
 }
 
 impl StandardFirework {
     /* Create new firework at random position on the bottom, with random colour. */
-    pub fn new(width: u32, height: u32) -> Self {
-        let (vel_min, vel_max) = vel_min_max(height);
-
+    pub fn new(settings: &Settings, random: &mut Random, width: u32, height: u32) -> Self {
         Self {
-            rocket: Particle::new(
-                TwoVec::new(Math::random() * width as f64, height as f64),
-                TwoVec::new(0., vel_min + (vel_max - vel_min) * Math::random()),
-            ),
+            rocket: new_rocket(settings, random, width, height),
             exploded: false,
             particles: Vec::new(),
-            colour: random_colour(),
-            lifetime: PARTICLE_LIFETIME,
+            colour: settings.palette.random_colour(random),
+            lifetime: settings.particle_lifetime,
         }
     }
 }
@@ -0,0 +1,73 @@
+use web_sys::CanvasRenderingContext2d;
+
+use super::colour::rgba_to_colour;
+use super::fireworks::Burst;
+use super::render::Sprite;
+use crate::error::Error;
+
+/* How much the sky lights up right by a burst which has just exploded. */
+const FLASH_ALPHA: f64 = 0.25;
+
+/* The distance from a burst at which it no longer lights up the sky. */
+const FLASH_RADIUS: f64 = 350.;
+
+/* Get the light a burst shines on the sky as a large dot. It is brightest as
+ * the shell explodes, and fades faster than the sparks. */
+pub fn flash(burst: &Burst) -> Sprite {
+    let alpha = FLASH_ALPHA * burst.intensity.powi(2);
+    let depth = burst.pos.z();
+
+    Sprite::dot(burst.pos.xy(), FLASH_RADIUS, burst.colour, alpha).at_depth(depth, depth)
+}
+
+/* Light up the sky behind everything drawn so far, fading out from the
+ * middle of every flash. The context should be transformed to logical
+ * pixels, and is left as it was, also when drawing fails. */
+pub fn draw_flashes(context: &CanvasRenderingContext2d, flashes: &[Sprite]) -> Result<(), Error> {
+    context.save();
+    let drawn = fill_flashes(context, flashes);
+    context.restore();
+
+    drawn
+}
+
+fn fill_flashes(context: &CanvasRenderingContext2d, flashes: &[Sprite]) -> Result<(), Error> {
+    context.set_global_composite_operation("destination-over")?;
+
+    for flash in flashes {
+        let (x, y) = flash.from.into();
+        let radius = flash.radius;
+
+        let gradient = context.create_radial_gradient(x, y, 0., x, y, radius)?;
+        gradient.add_color_stop(0., &rgba_to_colour(flash.colour, flash.alpha))?;
+        gradient.add_color_stop(1., &rgba_to_colour(flash.colour, 0.))?;
+
+        context.set_fill_style_canvas_gradient(&gradient);
+        context.fill_rect(x - radius, y - radius, radius * 2., radius * 2.);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::sim::ThreeVec;
+    use super::*;
+
+    #[test]
+    fn flashes_fade_faster_than_the_burst() {
+        let burst = |intensity| Burst {
+            pos: ThreeVec::new(10., 20., 30.),
+            colour: (255, 0, 0),
+            intensity,
+        };
+
+        let bright = flash(&burst(1.));
+        assert_eq!(bright.alpha, FLASH_ALPHA);
+        assert_eq!((bright.from.x(), bright.from.y()), (10., 20.));
+        assert_eq!(bright.from_depth, 30.);
+
+        assert_eq!(flash(&burst(0.5)).alpha, FLASH_ALPHA / 4.);
+        assert_eq!(flash(&burst(0.)).alpha, 0.);
+    }
+}
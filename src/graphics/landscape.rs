@@ -0,0 +1,343 @@
+use serde::{Deserialize, Serialize};
+use web_sys::CanvasRenderingContext2d;
+
+use super::colour;
+use super::colour::{rgba_to_colour, Colour};
+use super::fireworks::Burst;
+use super::random::Random;
+use super::sim::TwoVec;
+use crate::error::Error;
+
+const SILHOUETTE_COLOUR: Colour = (8, 8, 16);
+
+const WINDOW_COLOURS: [Colour; 3] = [(255, 214, 130), (255, 190, 90), (210, 225, 255)];
+
+/* The chance that a window of a building is lit. */
+const LIT_CHANCE: f64 = 0.3;
+
+/* The size of a window, and the space a window takes up including the wall
+ * around it. */
+const WINDOW_SIZE: (f64, f64) = (5., 7.);
+const WINDOW_SPACING: (f64, f64) = (11., 15.);
+
+/* The longest piece of the outline, so that every piece is lit by the bursts
+ * close to it rather than those close to its middle. */
+const PIECE_LENGTH: f64 = 20.;
+
+/* The distance from a burst at which it no longer lights up the edge of the
+ * silhouette. */
+const LIGHT_RADIUS: f64 = 300.;
+
+const EDGE_WIDTH: f64 = 2.5;
+
+/* The landscapes in front of which the show can take place. */
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Landscape {
+    #[default]
+    None,
+    City,
+    Hills,
+    Horizon,
+}
+
+impl Landscape {
+    /* Look up a landscape by the name it has in a configuration. */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Landscape::None),
+            "city" => Some(Landscape::City),
+            "hills" => Some(Landscape::Hills),
+            "horizon" => Some(Landscape::Horizon),
+            _ => None,
+        }
+    }
+
+    /* The name of the landscape in a configuration. */
+    pub fn name(&self) -> &'static str {
+        match self {
+            Landscape::None => "none",
+            Landscape::City => "city",
+            Landscape::Hills => "hills",
+            Landscape::Horizon => "horizon",
+        }
+    }
+}
+
+/* A lit window in the landscape, with the position of its top left corner. */
+#[derive(Clone, Serialize, Deserialize)]
+struct Window {
+    pos: TwoVec,
+    colour: Colour,
+}
+
+/* The dark silhouette of a landscape along the bottom of the canvas, which
+ * rockets rise from behind. Its edge lights up with the bursts close to it. */
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Foreground {
+    landscape: Landscape,
+    /* The top edge of the silhouette from the left of the canvas to the
+     * right. Everything below it is covered. */
+    outline: Vec<TwoVec>,
+    windows: Vec<Window>,
+}
+
+impl Foreground {
+    /* Create a random landscape of the given type for a canvas of the given
+     * size. */
+    pub fn generate(random: &mut Random, landscape: Landscape, width: u32, height: u32) -> Self {
+        let (width, height) = (width as f64, height as f64);
+        let mut foreground = Self {
+            landscape,
+            outline: Vec::new(),
+            windows: Vec::new(),
+        };
+
+        match landscape {
+            Landscape::None => {}
+            Landscape::City => foreground.build_city(random, width, height),
+            Landscape::Hills => foreground.raise_hills(random, width, height),
+            Landscape::Horizon => foreground.draw_horizon(random, width, height),
+        }
+
+        foreground.subdivide();
+        foreground
+    }
+
+    pub fn landscape(&self) -> Landscape {
+        self.landscape
+    }
+
+    /* Stretch the landscape along with the canvas. */
+    pub fn rescale(&mut self, x_scale: f64, y_scale: f64) {
+        let scale = |pos: &mut TwoVec| *pos = TwoVec::new(pos.x() * x_scale, pos.y() * y_scale);
+
+        self.outline.iter_mut().for_each(scale);
+        self.windows
+            .iter_mut()
+            .map(|window| &mut window.pos)
+            .for_each(scale);
+    }
+
+    /* Draw the silhouette over everything, with its edge lit by the bursts.
+     * The landscape is moved up from the bottom of a canvas of the given
+     * height to stand on the given ground, like the shore of the water. The
+     * context should be transformed to logical pixels. */
+    pub fn draw(
+        &self,
+        context: &CanvasRenderingContext2d,
+        height: f64,
+        ground: f64,
+        bursts: &[Burst],
+    ) -> Result<(), Error> {
+        let (first, last) = match (self.outline.first(), self.outline.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(()),
+        };
+
+        context.save();
+        context.translate(0., ground - height)?;
+
+        context.begin_path();
+        context.move_to(first.x(), height);
+        for point in &self.outline {
+            context.line_to(point.x(), point.y());
+        }
+        context.line_to(last.x(), height);
+        context.close_path();
+        context.set_fill_style_str(&rgba_to_colour(SILHOUETTE_COLOUR, 1.));
+        context.fill();
+
+        for window in &self.windows {
+            context.set_fill_style_str(&rgba_to_colour(window.colour, 0.9));
+            context.fill_rect(window.pos.x(), window.pos.y(), WINDOW_SIZE.0, WINDOW_SIZE.1);
+        }
+
+        context.set_line_cap("round");
+        context.set_line_width(EDGE_WIDTH);
+        let shift = TwoVec::new(0., ground - height);
+        for (from, to) in self.outline.iter().zip(&self.outline[1..]) {
+            if let Some((colour, alpha)) = edge_light(from.lerp(to, 0.5) + shift, bursts) {
+                context.begin_path();
+                context.set_stroke_style_str(&rgba_to_colour(colour, alpha));
+                context.move_to(from.x(), from.y());
+                context.line_to(to.x(), to.y());
+                context.stroke();
+            }
+        }
+
+        context.restore();
+
+        Ok(())
+    }
+
+    /* Put buildings of random width and height next to each other, with a
+     * grid of windows of which some are lit. */
+    fn build_city(&mut self, random: &mut Random, width: f64, height: f64) {
+        let mut x = 0.;
+
+        while x < width {
+            let building_width = 30. + random.uniform() * 60.;
+            let top = height * (0.92 - random.uniform() * 0.22);
+
+            self.outline.push(TwoVec::new(x, top));
+            self.outline.push(TwoVec::new(x + building_width, top));
+
+            let columns = ((building_width - 4.) / WINDOW_SPACING.0).floor() as u32;
+            let rows = ((height - top - 6.) / WINDOW_SPACING.1).floor() as u32;
+            let margin = (building_width - columns as f64 * WINDOW_SPACING.0) / 2.;
+
+            for row in 0..rows {
+                for column in 0..columns {
+                    if random.uniform() < LIT_CHANCE {
+                        let pos = TwoVec::new(
+                            x + margin + column as f64 * WINDOW_SPACING.0 + 3.,
+                            top + 6. + row as f64 * WINDOW_SPACING.1,
+                        );
+                        self.light_window(random, pos);
+                    }
+                }
+            }
+
+            x += building_width;
+        }
+    }
+
+    /* Roll hills over the bottom of the canvas, with the windows of a few
+     * houses lit on them. */
+    fn raise_hills(&mut self, random: &mut Random, width: f64, height: f64) {
+        let (phase1, phase2) = (random.uniform() * 10., random.uniform() * 10.);
+        let hill_height = |x: f64| {
+            height * (0.13 + 0.05 * (x / 230. + phase1).sin() + 0.03 * (x / 97. + phase2).sin())
+        };
+
+        let steps = (width / PIECE_LENGTH).ceil() as u32;
+        for step in 0..=steps {
+            let x = step as f64 * PIECE_LENGTH;
+            self.outline.push(TwoVec::new(x, height - hill_height(x)));
+        }
+
+        for _ in 0..(width / 150.).ceil() as u32 {
+            let x = random.uniform() * width;
+            self.light_window(random, TwoVec::new(x, height - hill_height(x) + 10.));
+        }
+    }
+
+    /* Lay a flat horizon low on the canvas, with distant lights along it. */
+    fn draw_horizon(&mut self, random: &mut Random, width: f64, height: f64) {
+        let horizon = height * 0.93;
+        self.outline.push(TwoVec::new(0., horizon));
+        self.outline.push(TwoVec::new(width, horizon));
+
+        for _ in 0..(width / 40.).ceil() as u32 {
+            let pos = TwoVec::new(
+                random.uniform() * width,
+                horizon + 3. + random.uniform() * (height - horizon) / 2.,
+            );
+            self.light_window(random, pos);
+        }
+    }
+
+    fn light_window(&mut self, random: &mut Random, pos: TwoVec) {
+        let colour = WINDOW_COLOURS[(random.uniform() * WINDOW_COLOURS.len() as f64) as usize];
+        self.windows.push(Window { pos, colour });
+    }
+
+    /* Split long pieces of the outline into pieces of at most PIECE_LENGTH. */
+    fn subdivide(&mut self) {
+        let mut outline = Vec::with_capacity(self.outline.len());
+
+        for (from, to) in self
+            .outline
+            .iter()
+            .zip(&self.outline[1.min(self.outline.len())..])
+        {
+            let pieces = ((to - from).length() / PIECE_LENGTH).ceil().max(1.) as u32;
+            outline.extend((0..pieces).map(|piece| from.lerp(to, piece as f64 / pieces as f64)));
+        }
+        outline.extend(self.outline.last());
+
+        self.outline = outline;
+    }
+}
+
+/* Get the colour and opacity of the light the bursts shine on a piece of the
+ * edge at the given position, if any. */
+fn edge_light(pos: TwoVec, bursts: &[Burst]) -> Option<(Colour, f64)> {
+    let mut light = (0, 0, 0);
+    let mut alpha = 0.;
+
+    for burst in bursts {
+        let distance = (pos - burst.pos.xy()).length();
+        let strength = burst.intensity * (1. - distance / LIGHT_RADIUS).max(0.);
+
+        light = colour::colour_add(&light, &colour::colour_mul(&burst.colour, strength));
+        alpha += strength;
+    }
+
+    (alpha > 0.).then(|| (light, alpha.min(1.)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::sim::ThreeVec;
+    use super::*;
+
+    const LANDSCAPES: [Landscape; 4] = [
+        Landscape::None,
+        Landscape::City,
+        Landscape::Hills,
+        Landscape::Horizon,
+    ];
+
+    #[test]
+    fn landscapes_are_found_by_name() {
+        for landscape in LANDSCAPES {
+            assert_eq!(Landscape::from_name(landscape.name()), Some(landscape));
+        }
+    }
+
+    #[test]
+    fn landscapes_span_the_bottom_of_the_canvas() {
+        let mut random = Random::seeded(11);
+
+        for landscape in &LANDSCAPES[1..] {
+            let foreground = Foreground::generate(&mut random, *landscape, 800, 600);
+            let outline = &foreground.outline;
+
+            assert_eq!(outline.first().unwrap().x(), 0.);
+            assert!(outline.last().unwrap().x() >= 800.);
+            assert!(!foreground.windows.is_empty());
+
+            for (from, to) in outline.iter().zip(&outline[1..]) {
+                assert!(to.x() >= from.x());
+                assert!((to - from).length() <= PIECE_LENGTH + 1e-9);
+            }
+
+            /* The landscape stays low, and its windows are in it. */
+            assert!(outline
+                .iter()
+                .all(|point| (400. ..600.).contains(&point.y())));
+            for window in &foreground.windows {
+                assert!(window.pos.y() > 400. && window.pos.y() + WINDOW_SIZE.1 < 600.);
+            }
+        }
+
+        assert!(Foreground::generate(&mut random, Landscape::None, 800, 600)
+            .outline
+            .is_empty());
+    }
+
+    #[test]
+    fn bursts_light_the_edge_close_to_them() {
+        let bursts = [Burst {
+            pos: ThreeVec::new(100., 100., 0.),
+            colour: (200, 100, 0),
+            intensity: 1.,
+        }];
+
+        let (colour, alpha) = edge_light(TwoVec::new(100., 250.), &bursts).unwrap();
+        assert_eq!((colour, alpha), ((100, 50, 0), 0.5));
+        assert!(edge_light(TwoVec::new(100., 400.), &bursts).is_none());
+    }
+}
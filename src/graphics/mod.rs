@@ -1,161 +1,245 @@
+mod beats;
+mod camera;
 mod colour;
+mod detail;
 mod fireworks;
+mod flash;
+mod float;
+mod landscape;
+mod random;
+mod render;
+mod scene;
 mod sim;
-
-use core::panic;
-
-use js_sys::Math;
-use wasm_bindgen::{JsCast, JsValue};
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
-
-use fireworks::{ColourShiftFirework, Firework, SparkleFirework, StandardFirework};
-use sim::{Particle, TwoVec};
-
-const STAR_RADIUS: f64 = 2.;
-const STAR_COUNT: u32 = 20;
-
+mod smoke;
+mod snapshot;
+mod sound;
+mod surface;
+mod water;
+
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::config::{Config, Settings};
+use crate::error::Error;
+use crate::replay::Event;
+pub use beats::STEPS_PER_SECOND;
+pub use colour::Palette;
+pub use detail::Detail;
+pub use fireworks::FireworkKind;
+pub use landscape::Landscape;
+pub use render::RendererKind;
+use render::{Canvas2dRenderer, Renderer, SoftwareRenderer, Sprite, WebGlRenderer};
+use scene::clamp_size;
+pub use scene::Scene;
+pub use sim::{Field, FieldKind, IntegratorKind};
+pub use snapshot::Snapshot;
+pub use sound::{SoundEvent, SoundKind};
+pub use surface::Surface;
+
+/* The simulation happens in logical pixels, which are CSS pixels when the
+ * canvas fills the page. The canvas itself has pixel_ratio times as many
+ * pixels, so the fireworks stay sharp on high density screens. */
 pub struct Graphics {
-    canvas: HtmlCanvasElement,
+    canvas: Surface,
     context: CanvasRenderingContext2d,
-    stars: Vec<Particle>,
-    fireworks: Vec<Box<dyn Firework>>,
+    renderer: Box<dyn Renderer>,
+    pixel_ratio: f64,
+    scene: Scene,
 }
 
 impl Graphics {
     /* Create a new firework simulation on the given canvas. */
-    pub fn new(canvas: HtmlCanvasElement) -> Self {
-        let context = canvas
-            .get_context("2d")
-            .unwrap()
-            .unwrap()
-            .dyn_into::<web_sys::CanvasRenderingContext2d>()
-            .unwrap();
-
-        let stars = Vec::new();
-        let fireworks = Vec::new();
-
-        Self {
+    pub fn new(canvas: Surface) -> Result<Self, Error> {
+        let context = canvas.context_2d()?;
+
+        Ok(Self {
+            scene: Scene::new(canvas.width(), canvas.height()),
+            pixel_ratio: 1.,
             canvas,
             context,
-            stars,
-            fireworks,
+            renderer: Box::new(Canvas2dRenderer),
+        })
+    }
+
+    /* Set the amount of canvas pixels per logical pixel, usually the
+     * devicePixelRatio of the window. */
+    pub fn set_pixel_ratio(&mut self, ratio: f64) {
+        if ratio.is_finite() && ratio > 0. && ratio != self.pixel_ratio {
+            self.pixel_ratio = ratio;
+            self.fit_canvas();
         }
     }
 
-    pub fn resize(&mut self, width: u32, height: u32) {
-        let (old_width, old_height) = (self.canvas.width(), self.canvas.height());
-        let width_ratio = width as f64 / old_width as f64;
-        let height_ratio = height as f64 / old_height as f64;
+    /* Switch to another way of drawing the sprites. If it can't be used, the
+     * renderer stays the same. */
+    pub fn set_renderer(&mut self, kind: RendererKind) -> Result<(), Error> {
+        self.renderer = match kind {
+            RendererKind::Canvas2d => Box::new(Canvas2dRenderer),
+            RendererKind::WebGl => Box::new(WebGlRenderer::new(&self.canvas)?),
+            RendererKind::Software => Box::new(SoftwareRenderer),
+        };
 
-        for star in &mut self.stars {
-            let old_pos = star.pos();
+        Ok(())
+    }
 
-            let (new_x, new_y) = (old_pos.x() * width_ratio, old_pos.y() * height_ratio);
+    pub fn renderer(&self) -> RendererKind {
+        self.renderer.kind()
+    }
 
-            star.set_pos(TwoVec::new(new_x, new_y));
-        }
+    /* Get the width of the canvas in logical pixels. */
+    pub fn width(&self) -> u32 {
+        self.scene.width()
+    }
+
+    /* Get the height of the canvas in logical pixels. */
+    pub fn height(&self) -> u32 {
+        self.scene.height()
+    }
+
+    pub fn pixel_ratio(&self) -> f64 {
+        self.pixel_ratio
+    }
+
+    /* Get the size the canvas is shown at on the page, if it is on one. */
+    pub fn client_size(&self) -> Option<(u32, u32)> {
+        self.canvas.client_size()
+    }
+
+    /* Get the configuration of the show, changes to it apply immediately. */
+    pub fn config(&self) -> Config {
+        self.scene.config()
+    }
+
+    /* Get the settings at the current level of detail. */
+    pub fn settings(&self) -> Settings {
+        self.scene.settings()
+    }
+
+    /* The maximal amount of fireworks on the canvas. */
+    pub fn max_fireworks(&self) -> u32 {
+        self.scene.max_fireworks()
+    }
+
+    /* Set the message shown in the middle of the canvas. */
+    pub fn set_message(&mut self, message: &str) {
+        self.scene.set_message(message);
+    }
+
+    /* Count down to the given time in milliseconds since the epoch before
+     * showing the message. */
+    pub fn set_countdown(&mut self, target: Option<f64>) {
+        self.scene.set_countdown(target);
+    }
 
-        self.canvas.set_width(width);
-        self.canvas.set_height(height);
-    }
-
-    /* Create the stars. */
-    pub fn init(&mut self) {
-        // Generate the stars
-        self.create_stars(STAR_COUNT, self.canvas.width(), self.canvas.height());
-    }
-
-    /* Spawn a firework, depending on what types of firework there are. */
-    pub fn spawn_firework(&mut self) {
-        match self.fireworks.len() % 4 {
-            0 => {
-                self.fireworks.push(Box::from(StandardFirework::new(
-                    self.canvas.width(),
-                    self.canvas.height(),
-                )));
-            }
-            1 => {
-                self.fireworks.push(Box::from(StandardFirework::new(
-                    self.canvas.width(),
-                    self.canvas.height(),
-                )));
-            }
-            2 => {
-                self.fireworks.push(Box::from(ColourShiftFirework::new(
-                    self.canvas.width(),
-                    self.canvas.height(),
-                )));
-            }
-            3 => {
-                self.fireworks.push(Box::from(SparkleFirework::new(
-                    self.canvas.width(),
-                    self.canvas.height(),
-                )));
-            }
-            _ => {
-                panic!("This shouldn't happen ever.");
-            }
+    /* Restart the random numbers of the show from the given seed, so the
+     * show which follows is the same every time. */
+    pub fn seed(&mut self, seed: u64) {
+        self.scene.seed(seed);
+    }
+
+    /* Replace the force fields which push the sparks around. */
+    pub fn set_fields(&mut self, fields: Vec<Field>) {
+        self.scene.set_fields(fields);
+    }
+
+    /* Check whether any of the fields follows the pointer. */
+    pub fn follows_pointer(&self) -> bool {
+        self.scene.follows_pointer()
+    }
+
+    /* Let an event from outside change the show. A new size is in logical
+     * pixels, and clamped to what a canvas can have. */
+    pub fn apply(&mut self, event: &Event) {
+        self.scene.apply(event);
+
+        if let Event::Resize { .. } = event {
+            self.fit_canvas();
         }
     }
 
-    /* Draw the firework and stars. */
-    pub fn draw(&self) {
-        /* Clear the canvas. */
+    /* Clear the canvas. */
+    pub fn clear(&self) {
+        let _ = self.context.set_transform(1., 0., 0., 1., 0., 0.);
         self.context.clear_rect(
             0.,
             0.,
             self.canvas.width() as f64,
             self.canvas.height() as f64,
         );
+    }
 
-        /* Draw the stars. */
-        self.draw_stars();
-
-        /* Draw the fireworks. */
-        for firework in &self.fireworks {
-            firework.draw(&self.context);
+    /* Draw the firework and stars. */
+    pub fn draw(&mut self) {
+        let settings = self.settings();
+        let (sprites, bursts) = self.scene.sprites(&settings);
+
+        self.clear();
+
+        /* When a renderer fails, for instance because WebGL was lost, fall
+         * back to the 2D canvas for good. */
+        if let Err(err) = self.draw_sprites(&sprites) {
+            web_sys::console::warn_1(&JsValue::from_str(&format!(
+                "Falling back to 2d drawing: {}",
+                err
+            )));
+
+            self.renderer = Box::new(Canvas2dRenderer);
+            let _ = self.draw_sprites(&sprites);
         }
 
-        self.context.set_fill_style(&JsValue::from_str("yellow"));
-        self.context.set_text_baseline("middle");
-        self.context.set_text_align("center");
-        self.context.set_font("70px sans-serif");
-
-        self.context
-            .fill_text(
-                "Gelukkig Nieuwjaar!",
-                self.canvas.width() as f64 / 2.,
-                self.canvas.height() as f64 / 2.,
-            )
-            .unwrap();
+        let ratio = self.pixel_ratio;
+        let _ = self.context.set_transform(ratio, 0., 0., ratio, 0., 0.);
+        self.scene.draw_overlay(&self.context, &settings, &bursts);
     }
 
     /* Simulate the fireworks. */
     pub fn step(&mut self) {
-        for firework in &mut self.fireworks {
-            firework.step(self.canvas.width(), self.canvas.height());
-        }
+        self.scene.step();
     }
 
-    /* Create stars at random positions on the canvas. */
-    fn create_stars(&mut self, count: u32, canvas_width: u32, canvas_height: u32) {
-        for _ in 0..count {
-            let pos = Particle::new(
-                TwoVec::new(
-                    Math::random() * canvas_width as f64,
-                    Math::random() * canvas_height as f64,
-                ),
-                TwoVec::zero(),
-            );
+    /* Save the complete state of the show. */
+    pub fn snapshot(&self) -> Snapshot {
+        self.scene.snapshot()
+    }
 
-            self.stars.push(pos);
-        }
+    /* Continue the show from a snapshot. A snapshot of a canvas of another
+     * size is stretched to fit this one. */
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), String> {
+        self.scene.restore(snapshot)?;
+        self.fit_canvas();
+
+        Ok(())
     }
 
-    /* This function draws the stars on the canvas. */
-    fn draw_stars(&self) {
-        for star in &self.stars {
-            star.draw(&self.context, colour::YELLOW, STAR_RADIUS);
-        }
+    /* Continue the show from a snapshot, at the size of the canvas it was
+     * taken from. */
+    pub fn restore_exactly(&mut self, snapshot: Snapshot) -> Result<(), String> {
+        self.scene.restore_exactly(snapshot)?;
+        self.fit_canvas();
+
+        Ok(())
+    }
+
+    /* Take the sounds made since the last time this was called. */
+    pub fn take_sounds(&mut self) -> Vec<SoundEvent> {
+        self.scene.take_sounds()
+    }
+
+    /* Give the canvas as many pixels as are drawn at the pixel ratio. */
+    fn fit_canvas(&self) {
+        let (width, height) = (
+            self.width() as f64 * self.pixel_ratio,
+            self.height() as f64 * self.pixel_ratio,
+        );
+
+        self.canvas.set_size(
+            clamp_size(width.round() as u32),
+            clamp_size(height.round() as u32),
+        );
+    }
+
+    fn draw_sprites(&self, sprites: &[Sprite]) -> Result<(), Error> {
+        self.renderer
+            .draw(&self.context, &self.canvas, sprites, self.pixel_ratio)
     }
 }
@@ -0,0 +1,91 @@
+#[cfg(target_arch = "wasm32")]
+use js_sys::Math;
+
+/* A generator of random numbers, using xorshift64*. Every show has its own,
+ * so seeding or restoring one show doesn't change the course of another. */
+#[derive(Clone, Debug)]
+pub struct Random {
+    /* The state of the generator, which should never be zero. */
+    state: u64,
+}
+
+impl Random {
+    /* Start from a random state in the browser. Elsewhere, like in tests,
+     * every run starts the same. */
+    #[cfg(target_arch = "wasm32")]
+    pub fn new() -> Self {
+        Self::seeded((Math::random() * u64::MAX as f64) as u64)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new() -> Self {
+        Self::seeded(0)
+    }
+
+    /* Start from the given seed, so the show which follows is the same
+     * every time. */
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            state: scramble(seed),
+        }
+    }
+
+    /* Continue the random numbers from a state given by state(). */
+    pub fn from_state(state: u64) -> Self {
+        Self {
+            state: state.max(1),
+        }
+    }
+
+    /* Get the state of the generator, to continue from it later. */
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /* Generate a random number between 0 and 1. */
+    pub fn uniform(&mut self) -> f64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+
+        /* Use the top 53 bits, which fit exactly in a float. */
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/* Spread the bits of a seed, so that similar seeds give different sequences
+ * and a seed of zero still gives a valid state. */
+fn scramble(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+    (z ^ (z >> 31)).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generators_do_not_disturb_each_other() {
+        let mut alone = Random::seeded(7);
+        let expected: Vec<f64> = (0..10).map(|_| alone.uniform()).collect();
+
+        let (mut first, mut second) = (Random::seeded(7), Random::seeded(8));
+        let numbers: Vec<f64> = (0..10)
+            .map(|_| {
+                second.uniform();
+                first.uniform()
+            })
+            .collect();
+        assert_eq!(numbers, expected);
+
+        /* A generator continues where its state left off. */
+        let mut resumed = Random::from_state(first.state());
+        assert_eq!(resumed.uniform(), alone.uniform());
+        assert!(expected.iter().all(|number| (0. ..1.).contains(number)));
+    }
+}
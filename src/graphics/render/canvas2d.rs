@@ -0,0 +1,51 @@
+use web_sys::CanvasRenderingContext2d;
+
+use super::super::colour::rgba_to_colour;
+use super::super::surface::Surface;
+use super::{Renderer, RendererKind, Sprite};
+use crate::error::Error;
+
+/* This renderer draws every sprite as a path on the canvas. It works
+ * everywhere, but is slow for many sprites. */
+pub struct Canvas2dRenderer;
+
+impl Renderer for Canvas2dRenderer {
+    fn kind(&self) -> RendererKind {
+        RendererKind::Canvas2d
+    }
+
+    fn draw(
+        &self,
+        context: &CanvasRenderingContext2d,
+        _canvas: &Surface,
+        sprites: &[Sprite],
+        pixel_ratio: f64,
+    ) -> Result<(), Error> {
+        context.set_transform(pixel_ratio, 0., 0., pixel_ratio, 0., 0.)?;
+        context.set_line_cap("round");
+
+        for sprite in sprites {
+            context.begin_path();
+
+            if sprite.is_dot() {
+                context.set_fill_style_str(&rgba_to_colour(sprite.colour, sprite.alpha));
+                context.arc(
+                    sprite.from.x(),
+                    sprite.from.y(),
+                    sprite.radius,
+                    0.,
+                    std::f64::consts::TAU,
+                )?;
+                context.fill();
+            } else {
+                context.set_stroke_style_str(&rgba_to_colour(sprite.colour, sprite.alpha));
+                context.set_line_width(sprite.radius * 2.);
+                context.move_to(sprite.from.x(), sprite.from.y());
+                context.line_to(sprite.to.x(), sprite.to.y());
+                context.stroke();
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+/* Golden image tests: frames of every type of firework are drawn with the
+ * software renderer at fixed seeds, and compared against the reference
+ * images in tests/golden. When a frame differs visibly, the frame and an
+ * image of the differences are written to target/golden.
+ *
+ * After an intended change to the drawing, the references are updated by
+ * running the tests with UPDATE_GOLDEN=1. */
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use super::super::camera::Camera;
+use super::super::fireworks::{
+    launch_vel, ColourShiftFirework, Firework, SparkleFirework, StandardFirework,
+};
+use super::super::random::Random;
+use super::super::sim::Fields;
+use super::super::water::Water;
+use super::software::Raster;
+use super::Sprite;
+use crate::config::Settings;
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 320;
+
+/* The colour differences which count as visible, on a scale from 0 to 1.
+ * Smaller differences, like from rounding on another machine, are ignored. */
+const THRESHOLD: f64 = 0.1;
+
+/* The fraction of pixels which may differ visibly. */
+const MAX_DIFFERENT: f64 = 0.001;
+
+/* Launch a single firework in the middle of the canvas so it explodes after
+ * a fixed amount of steps, and draw it after the given amount of steps. */
+fn render(
+    name: &str,
+    create: fn(&Settings, &mut Random, u32, u32) -> Box<dyn Firework>,
+    random_seed: u64,
+    steps: u32,
+    pixel_ratio: f64,
+) {
+    render_with(name, detailed(), create, random_seed, steps, pixel_ratio);
+}
+
+/* The settings the references are drawn with: the defaults, with the glow
+ * and trails which are off by default, so they are checked too. */
+fn detailed() -> Settings {
+    Settings {
+        trail_length: 2.,
+        glow: true,
+        ..Settings::default()
+    }
+}
+
+/* Render a firework like render does, with the given settings. In 3D, the
+ * firework goes up at a random depth and is seen through a camera which has
+ * not orbited yet, and water has calm ripples. */
+fn render_with(
+    name: &str,
+    settings: Settings,
+    create: fn(&Settings, &mut Random, u32, u32) -> Box<dyn Firework>,
+    random_seed: u64,
+    steps: u32,
+    pixel_ratio: f64,
+) {
+    let mut random = Random::seeded(random_seed);
+
+    let mut firework = create(&settings, &mut random, WIDTH, HEIGHT);
+    firework.launch(
+        &settings,
+        &mut random,
+        WIDTH as f64 / 2.,
+        WIDTH,
+        HEIGHT,
+        launch_vel(&settings, 60),
+    );
+
+    for _ in 0..steps {
+        firework.step(&settings, &mut random, &Fields::default(), WIDTH, HEIGHT);
+    }
+
+    let mut sprites: Vec<Sprite> = Vec::new();
+    firework.draw(&settings, &mut sprites);
+
+    if settings.three_d {
+        sprites = sprites
+            .iter()
+            .filter_map(|sprite| Camera::default().project(sprite, WIDTH, HEIGHT))
+            .collect();
+    }
+
+    if let Some(water_line) = settings.water_line(HEIGHT) {
+        Water::default().reflect(&mut sprites, water_line, HEIGHT as f64);
+    }
+
+    let mut raster = Raster::new(
+        (WIDTH as f64 * pixel_ratio) as u32,
+        (HEIGHT as f64 * pixel_ratio) as u32,
+    );
+    raster.draw(&sprites, pixel_ratio);
+
+    check(name, &raster);
+}
+
+fn standard(
+    settings: &Settings,
+    random: &mut Random,
+    width: u32,
+    height: u32,
+) -> Box<dyn Firework> {
+    Box::new(StandardFirework::new(settings, random, width, height))
+}
+
+fn colour_shift(
+    settings: &Settings,
+    random: &mut Random,
+    width: u32,
+    height: u32,
+) -> Box<dyn Firework> {
+    Box::new(ColourShiftFirework::new(settings, random, width, height))
+}
+
+fn sparkle(settings: &Settings, random: &mut Random, width: u32, height: u32) -> Box<dyn Firework> {
+    Box::new(SparkleFirework::new(settings, random, width, height))
+}
+
+#[test]
+fn rocket() {
+    render("rocket", standard, 1, 30, 1.);
+}
+
+#[test]
+fn standard_explosion() {
+    render("standard_explosion", standard, 1, 65, 1.);
+}
+
+#[test]
+fn standard_fading() {
+    render("standard_fading", standard, 2, 110, 1.);
+}
+
+#[test]
+fn colour_shift_explosion() {
+    render("colour_shift_explosion", colour_shift, 3, 70, 1.);
+}
+
+#[test]
+fn colour_shift_fading() {
+    render("colour_shift_fading", colour_shift, 4, 110, 1.);
+}
+
+#[test]
+fn sparkle_explosion() {
+    render("sparkle_explosion", sparkle, 5, 70, 1.);
+}
+
+#[test]
+fn sparkle_fading() {
+    render("sparkle_fading", sparkle, 6, 110, 1.);
+}
+
+#[test]
+fn high_density() {
+    render("high_density", standard, 7, 75, 2.);
+}
+
+#[test]
+fn sphere_explosion() {
+    let settings = Settings {
+        three_d: true,
+        particle_count: 200,
+        ..detailed()
+    };
+    render_with("sphere_explosion", settings, standard, 8, 80, 1.);
+}
+
+#[test]
+fn reflection() {
+    let settings = Settings {
+        reflection: true,
+        ..detailed()
+    };
+    render_with("reflection", settings, colour_shift, 9, 75, 1.);
+}
+
+/* Compare a frame against its reference image. */
+fn check(name: &str, raster: &Raster) {
+    let (width, height) = (raster.width(), raster.height());
+    let actual = on_black(&raster.to_rgba());
+    let reference_path = golden_dir().join(format!("{}.png", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(golden_dir()).unwrap();
+        write_png(&reference_path, width, height, &actual);
+        return;
+    }
+
+    let out_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden");
+    let fail = |message: String| {
+        fs::create_dir_all(&out_dir).unwrap();
+        let actual_path = out_dir.join(format!("{}.png", name));
+        write_png(&actual_path, width, height, &actual);
+
+        panic!("{} The frame is in {}.", message, actual_path.display());
+    };
+
+    let reference = match read_png(&reference_path) {
+        Some(reference) => reference,
+        None => fail(format!(
+            "There is no reference image {}, create it with UPDATE_GOLDEN=1.",
+            reference_path.display()
+        )),
+    };
+    if (reference.0, reference.1) != (width, height) {
+        fail(format!(
+            "The frame is {}x{}, but the reference image is {}x{}.",
+            width, height, reference.0, reference.1
+        ));
+    }
+
+    let (different, diff) = compare(&reference.2, &actual);
+    if different as f64 > MAX_DIFFERENT * (width * height) as f64 {
+        let diff_path = out_dir.join(format!("{}.diff.png", name));
+        fs::create_dir_all(&out_dir).unwrap();
+        write_png(&diff_path, width, height, &diff);
+
+        fail(format!(
+            "{} of {} pixels differ from {}, see {}.",
+            different,
+            width * height,
+            reference_path.display(),
+            diff_path.display()
+        ));
+    }
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/* Put an image on the black of the night sky, leaving red, green and blue. */
+fn on_black(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .flat_map(|pixel| {
+            let alpha = pixel[3] as u32;
+            (0..3).map(move |channel| ((pixel[channel] as u32 * alpha + 127) / 255) as u8)
+        })
+        .collect()
+}
+
+/* Count the pixels which differ visibly, and draw an image of them: the
+ * reference dimmed, with visible differences in red and invisible ones in
+ * blue. */
+fn compare(reference: &[u8], actual: &[u8]) -> (usize, Vec<u8>) {
+    let mut different = 0;
+    let mut diff = Vec::with_capacity(reference.len());
+
+    for (expected, pixel) in reference.chunks_exact(3).zip(actual.chunks_exact(3)) {
+        let distance = perceptual_distance(expected, pixel);
+
+        if distance > THRESHOLD {
+            different += 1;
+            diff.extend_from_slice(&[255, 0, 0]);
+        } else if distance > 0. {
+            diff.extend_from_slice(&[0, 0, 160]);
+        } else {
+            let grey = (expected.iter().map(|&c| c as u32).sum::<u32>() / 9) as u8;
+            diff.extend_from_slice(&[grey; 3]);
+        }
+    }
+
+    (different, diff)
+}
+
+/* The distance between two colours as people see it, from 0 to 1. The
+ * colours are compared in YIQ, which weighs brightness more than hue. */
+fn perceptual_distance(a: &[u8], b: &[u8]) -> f64 {
+    let yiq = |c: &[u8]| {
+        let (r, g, b) = (c[0] as f64, c[1] as f64, c[2] as f64);
+        (
+            0.29889531 * r + 0.58662247 * g + 0.11448223 * b,
+            0.59597799 * r - 0.27417610 * g - 0.32180189 * b,
+            0.21147017 * r - 0.52261711 * g + 0.31114694 * b,
+        )
+    };
+    let (a, b) = (yiq(a), yiq(b));
+    let (y, i, q) = (a.0 - b.0, a.1 - b.1, a.2 - b.2);
+
+    /* The largest possible difference is between black and white. */
+    ((0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / 35215.).sqrt()
+}
+
+fn read_png(path: &Path) -> Option<(u32, u32, Vec<u8>)> {
+    let mut reader = png::Decoder::new(File::open(path).ok()?).read_info().ok()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).ok()?;
+
+    if info.color_type != png::ColorType::Rgb || info.bit_depth != png::BitDepth::Eight {
+        return None;
+    }
+    data.truncate(info.buffer_size());
+
+    Some((info.width, info.height, data))
+}
+
+fn write_png(path: &Path, width: u32, height: u32, rgb: &[u8]) {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path).unwrap()), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(rgb).unwrap();
+}
@@ -0,0 +1,116 @@
+mod canvas2d;
+#[cfg(test)]
+mod golden;
+mod software;
+mod webgl;
+
+use web_sys::CanvasRenderingContext2d;
+
+use super::colour::Colour;
+use super::sim::TwoVec;
+use super::surface::Surface;
+use crate::error::Error;
+pub use canvas2d::Canvas2dRenderer;
+pub use software::SoftwareRenderer;
+pub use webgl::WebGlRenderer;
+
+/* A round blob of light between two points, or a dot when both points are
+ * the same. Everything in a show except the text is drawn as sprites.
+ *
+ * Sprites of a show in 3D have their ends at some depth behind the canvas,
+ * until a camera projects them onto it. Renderers ignore the depth. */
+#[derive(Clone, Copy)]
+pub struct Sprite {
+    pub from: TwoVec,
+    pub to: TwoVec,
+    pub radius: f64,
+    pub colour: Colour,
+    pub alpha: f64,
+    pub from_depth: f64,
+    pub to_depth: f64,
+}
+
+impl Sprite {
+    /* Create a dot at the given position. */
+    pub const fn dot(pos: TwoVec, radius: f64, colour: Colour, alpha: f64) -> Self {
+        Self {
+            from: pos,
+            to: pos,
+            radius,
+            colour,
+            alpha,
+            from_depth: 0.,
+            to_depth: 0.,
+        }
+    }
+
+    /* Create a line with round ends between two points. */
+    pub const fn line(from: TwoVec, to: TwoVec, width: f64, colour: Colour, alpha: f64) -> Self {
+        Self {
+            from,
+            to,
+            radius: width / 2.,
+            colour,
+            alpha,
+            from_depth: 0.,
+            to_depth: 0.,
+        }
+    }
+
+    /* Move the ends of the sprite to the given depths behind the canvas. */
+    pub const fn at_depth(self, from_depth: f64, to_depth: f64) -> Self {
+        Self {
+            from_depth,
+            to_depth,
+            ..self
+        }
+    }
+
+    /* Check whether the sprite is a dot rather than a line. */
+    pub fn is_dot(&self) -> bool {
+        self.from == self.to
+    }
+}
+
+/* The ways sprites can be drawn. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RendererKind {
+    Canvas2d,
+    WebGl,
+    Software,
+}
+
+impl RendererKind {
+    /* Look up a renderer by the name it has in a configuration. */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "2d" => Some(RendererKind::Canvas2d),
+            "webgl" => Some(RendererKind::WebGl),
+            "software" => Some(RendererKind::Software),
+            _ => None,
+        }
+    }
+
+    /* The name of the renderer in a configuration. */
+    pub fn name(&self) -> &'static str {
+        match self {
+            RendererKind::Canvas2d => "2d",
+            RendererKind::WebGl => "webgl",
+            RendererKind::Software => "software",
+        }
+    }
+}
+
+/* Something which draws sprites onto the 2D context of a canvas. Sprites are
+ * given in logical pixels, which are pixel_ratio canvas pixels each. */
+pub trait Renderer {
+    fn kind(&self) -> RendererKind;
+
+    fn draw(
+        &self,
+        context: &CanvasRenderingContext2d,
+        canvas: &Surface,
+        sprites: &[Sprite],
+        pixel_ratio: f64,
+    ) -> Result<(), Error>;
+}
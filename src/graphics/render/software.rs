@@ -0,0 +1,208 @@
+use wasm_bindgen::Clamped;
+use web_sys::{CanvasRenderingContext2d, ImageData};
+
+use super::super::surface::Surface;
+use super::webgl::{instance_data, FLOATS_PER_SPRITE};
+use super::{Renderer, RendererKind, Sprite};
+use crate::error::Error;
+
+/* An image in memory which sprites are drawn into the same way the WebGL
+ * renderer draws them, adding up their light. The pixels hold premultiplied
+ * red, green, blue and alpha. */
+pub struct Raster {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Raster {
+    /* Create a transparent image of the given size in pixels. */
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0.; 4]; width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /* Draw sprites given in logical pixels, which are pixel_ratio pixels of
+     * the image each. */
+    pub fn draw(&mut self, sprites: &[Sprite], pixel_ratio: f64) {
+        for sprite in instance_data(sprites).chunks_exact(FLOATS_PER_SPRITE) {
+            self.draw_sprite(sprite, pixel_ratio as f32);
+        }
+    }
+
+    /* Draw a single sprite laid out like in the instance buffer, following
+     * the fragment shader of the WebGL renderer. */
+    fn draw_sprite(&mut self, sprite: &[f32], ratio: f32) {
+        let (x0, y0, x1, y1, radius) = (sprite[0], sprite[1], sprite[2], sprite[3], sprite[4]);
+        let (colour, alpha) = ([sprite[5], sprite[6], sprite[7]], sprite[8]);
+
+        let pixel = 1. / ratio;
+        let extent = radius + pixel;
+        let range = |from: f32, to: f32, size: u32| {
+            let start = ((from.min(to) - extent) * ratio).floor().max(0.);
+            let end = ((from.max(to) + extent) * ratio).ceil().min(size as f32);
+            (start as u32)..(end.max(start) as u32)
+        };
+
+        let (axis_x, axis_y) = (x1 - x0, y1 - y0);
+        let len2 = axis_x * axis_x + axis_y * axis_y;
+
+        for py in range(y0, y1, self.height) {
+            for px in range(x0, x1, self.width) {
+                let (x, y) = ((px as f32 + 0.5) * pixel, (py as f32 + 0.5) * pixel);
+
+                let t = if len2 > 0. {
+                    (((x - x0) * axis_x + (y - y0) * axis_y) / len2).clamp(0., 1.)
+                } else {
+                    0.
+                };
+                let dist = (x - (x0 + axis_x * t)).hypot(y - (y0 + axis_y * t));
+
+                /* Smooth the edge over a single pixel of the image. */
+                let coverage = ((radius - dist) / pixel + 0.5).clamp(0., 1.);
+                if coverage <= 0. {
+                    continue;
+                }
+
+                let weight = alpha * coverage;
+                let target = &mut self.pixels[(py * self.width + px) as usize];
+                for (channel, value) in target.iter_mut().zip(colour) {
+                    *channel += value * weight;
+                }
+                target[3] += weight;
+            }
+        }
+    }
+
+    /* Get the pixels as bytes of red, green, blue and straight alpha, the
+     * way image data stores them. */
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.pixels.len() * 4);
+
+        for pixel in &self.pixels {
+            let alpha = pixel[3].min(1.);
+            for channel in &pixel[..3] {
+                let value = if alpha > 0. {
+                    channel.min(1.) / alpha
+                } else {
+                    0.
+                };
+                data.push((value.min(1.) * 255.).round() as u8);
+            }
+            data.push((alpha * 255.).round() as u8);
+        }
+
+        data
+    }
+}
+
+/* This renderer draws the sprites into an image in memory, which is then put
+ * on the canvas. It works without a GPU and gives the same picture on every
+ * machine, but is slow for large canvases. */
+pub struct SoftwareRenderer;
+
+impl Renderer for SoftwareRenderer {
+    fn kind(&self) -> RendererKind {
+        RendererKind::Software
+    }
+
+    fn draw(
+        &self,
+        context: &CanvasRenderingContext2d,
+        canvas: &Surface,
+        sprites: &[Sprite],
+        pixel_ratio: f64,
+    ) -> Result<(), Error> {
+        let mut raster = Raster::new(canvas.width(), canvas.height());
+        raster.draw(sprites, pixel_ratio);
+
+        let image = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&raster.to_rgba()),
+            raster.width(),
+            raster.height(),
+        )?;
+        context.put_image_data(&image, 0., 0.)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::super::super::sim::TwoVec;
+    use super::*;
+
+    fn pixel(raster: &Raster, x: u32, y: u32) -> [u8; 4] {
+        let start = ((y * raster.width() + x) * 4) as usize;
+        raster.to_rgba()[start..start + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn dots_cover_their_radius() {
+        let mut raster = Raster::new(20, 20);
+        raster.draw(
+            &[Sprite::dot(TwoVec::new(10., 10.), 3., (255, 0, 0), 1.)],
+            1.,
+        );
+
+        assert_eq!(pixel(&raster, 10, 10), [255, 0, 0, 255]);
+        assert_eq!(pixel(&raster, 10, 15), [0, 0, 0, 0]);
+        assert_eq!(pixel(&raster, 0, 0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn light_adds_up() {
+        let mut raster = Raster::new(4, 4);
+        let dot = |colour| Sprite::dot(TwoVec::new(2., 2.), 2., colour, 0.5);
+        raster.draw(&[dot((255, 0, 0)), dot((0, 0, 255))], 1.);
+
+        assert_eq!(pixel(&raster, 1, 1), [128, 0, 128, 255]);
+    }
+
+    #[test]
+    fn pixel_ratio_scales_the_sprites() {
+        let mut raster = Raster::new(40, 40);
+        raster.draw(
+            &[Sprite::line(
+                TwoVec::new(2., 10.),
+                TwoVec::new(18., 10.),
+                2.,
+                (255, 255, 255),
+                1.,
+            )],
+            2.,
+        );
+
+        assert_eq!(pixel(&raster, 20, 19)[3], 255);
+        assert_eq!(pixel(&raster, 20, 23)[3], 0);
+        assert_eq!(pixel(&raster, 38, 20)[3], 0);
+    }
+
+    #[test]
+    fn sprites_outside_are_cut_off() {
+        let mut raster = Raster::new(10, 10);
+        raster.draw(
+            &[
+                Sprite::dot(TwoVec::new(-50., 5.), 3., (255, 255, 255), 1.),
+                Sprite::dot(TwoVec::new(0., 0.), 3., (255, 255, 255), 1.),
+            ],
+            1.,
+        );
+
+        assert_eq!(pixel(&raster, 0, 0)[3], 255);
+        assert_eq!(pixel(&raster, 9, 9)[3], 0);
+    }
+}
@@ -0,0 +1,327 @@
+use js_sys::Float32Array;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    CanvasRenderingContext2d, WebGl2RenderingContext as Gl, WebGlBuffer, WebGlProgram, WebGlShader,
+    WebGlUniformLocation, WebGlVertexArrayObject,
+};
+
+use super::super::surface::Surface;
+use super::{Renderer, RendererKind, Sprite};
+use crate::error::Error;
+
+/* The amount of floats per sprite in the instance buffer: the two ends, the
+ * radius and the colour with its alpha. */
+pub const FLOATS_PER_SPRITE: usize = 9;
+
+/* The attributes of a sprite in the instance buffer: their location in the
+ * vertex shader, their amount of floats and their offset in floats. */
+const ATTRIBUTES: [(u32, i32, i32); 3] = [(0, 4, 0), (1, 1, 4), (2, 4, 5)];
+
+/* Every sprite is a quad around its ends, the fragment shader cuts the round
+ * shape out of it. The corners of the quad follow from the vertex index. */
+const VERTEX_SHADER: &str = r#"#version 300 es
+layout(location = 0) in vec4 a_ends;
+layout(location = 1) in float a_radius;
+layout(location = 2) in vec4 a_colour;
+
+uniform vec2 u_resolution;
+uniform float u_pixel;
+
+out vec2 v_pos;
+flat out vec4 v_ends;
+flat out float v_radius;
+flat out vec4 v_colour;
+
+void main() {
+    vec2 axis = a_ends.zw - a_ends.xy;
+    float len = length(axis);
+    vec2 dir = len > 0.0 ? axis / len : vec2(1.0, 0.0);
+    vec2 normal = vec2(-dir.y, dir.x);
+
+    vec2 corner = vec2(float(gl_VertexID & 1), float(gl_VertexID >> 1)) * 2.0 - 1.0;
+    float extent = a_radius + u_pixel;
+    vec2 end = corner.x < 0.0 ? a_ends.xy : a_ends.zw;
+    vec2 pos = end + (dir * corner.x + normal * corner.y) * extent;
+
+    v_pos = pos;
+    v_ends = a_ends;
+    v_radius = a_radius;
+    v_colour = a_colour;
+
+    vec2 clip = pos / u_resolution * 2.0 - 1.0;
+    gl_Position = vec4(clip.x, -clip.y, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 300 es
+precision highp float;
+
+uniform float u_pixel;
+
+in vec2 v_pos;
+flat in vec4 v_ends;
+flat in float v_radius;
+flat in vec4 v_colour;
+
+out vec4 colour;
+
+void main() {
+    vec2 axis = v_ends.zw - v_ends.xy;
+    float len2 = dot(axis, axis);
+    float t = len2 > 0.0 ? clamp(dot(v_pos - v_ends.xy, axis) / len2, 0.0, 1.0) : 0.0;
+    float dist = length(v_pos - (v_ends.xy + axis * t));
+
+    /* Smooth the edge over a single pixel of the screen. */
+    float coverage = clamp((v_radius - dist) / u_pixel + 0.5, 0.0, 1.0);
+
+    colour = vec4(v_colour.rgb, v_colour.a * coverage);
+}
+"#;
+
+/* This renderer draws all sprites at once with WebGL 2, adding up their
+ * light. It draws on a canvas of its own, which is copied onto the canvas of
+ * the show. */
+pub struct WebGlRenderer {
+    canvas: Surface,
+    gl: Gl,
+    program: WebGlProgram,
+    vertex_array: WebGlVertexArrayObject,
+    instances: WebGlBuffer,
+    resolution: WebGlUniformLocation,
+    pixel: WebGlUniformLocation,
+}
+
+impl WebGlRenderer {
+    /* Set up WebGL 2 on a new canvas like the one of the show. */
+    pub fn new(target: &Surface) -> Result<Self, Error> {
+        let canvas = target.create_sibling()?;
+
+        let gl = canvas
+            .get_context("webgl2")?
+            .ok_or(Error::NoWebGl)?
+            .dyn_into::<Gl>()
+            .map_err(|_| Error::NoWebGl)?;
+
+        let program = link_program(
+            &gl,
+            &compile_shader(&gl, Gl::VERTEX_SHADER, VERTEX_SHADER)?,
+            &compile_shader(&gl, Gl::FRAGMENT_SHADER, FRAGMENT_SHADER)?,
+        )?;
+
+        let resolution = gl
+            .get_uniform_location(&program, "u_resolution")
+            .ok_or(Error::NoWebGl)?;
+        let pixel = gl
+            .get_uniform_location(&program, "u_pixel")
+            .ok_or(Error::NoWebGl)?;
+
+        let vertex_array = gl.create_vertex_array().ok_or(Error::NoWebGl)?;
+        let instances = gl.create_buffer().ok_or(Error::NoWebGl)?;
+
+        gl.bind_vertex_array(Some(&vertex_array));
+        gl.bind_buffer(Gl::ARRAY_BUFFER, Some(&instances));
+
+        /* The ends, the radius and the colour of every sprite. */
+        let stride = (FLOATS_PER_SPRITE * 4) as i32;
+        for (location, size, offset) in ATTRIBUTES {
+            gl.enable_vertex_attrib_array(location);
+            gl.vertex_attrib_pointer_with_i32(location, size, Gl::FLOAT, false, stride, offset * 4);
+            gl.vertex_attrib_divisor(location, 1);
+        }
+
+        gl.bind_vertex_array(None);
+
+        Ok(Self {
+            canvas,
+            gl,
+            program,
+            vertex_array,
+            instances,
+            resolution,
+            pixel,
+        })
+    }
+}
+
+impl Renderer for WebGlRenderer {
+    fn kind(&self) -> RendererKind {
+        RendererKind::WebGl
+    }
+
+    fn draw(
+        &self,
+        context: &CanvasRenderingContext2d,
+        target: &Surface,
+        sprites: &[Sprite],
+        pixel_ratio: f64,
+    ) -> Result<(), Error> {
+        let gl = &self.gl;
+        if gl.is_context_lost() {
+            return Err(Error::NoWebGl);
+        }
+
+        /* Draw at the resolution of the canvas of the show. */
+        let (width, height) = (target.width(), target.height());
+        if (self.canvas.width(), self.canvas.height()) != (width, height) {
+            self.canvas.set_size(width, height);
+        }
+
+        gl.viewport(0, 0, width as i32, height as i32);
+        gl.clear_color(0., 0., 0., 0.);
+        gl.clear(Gl::COLOR_BUFFER_BIT);
+
+        if !sprites.is_empty() {
+            gl.enable(Gl::BLEND);
+            gl.blend_func_separate(Gl::SRC_ALPHA, Gl::ONE, Gl::ONE, Gl::ONE);
+
+            let ([logical_width, logical_height], pixel) = view(width, height, pixel_ratio);
+            gl.use_program(Some(&self.program));
+            gl.uniform2f(Some(&self.resolution), logical_width, logical_height);
+            gl.uniform1f(Some(&self.pixel), pixel);
+
+            gl.bind_vertex_array(Some(&self.vertex_array));
+            gl.bind_buffer(Gl::ARRAY_BUFFER, Some(&self.instances));
+            gl.buffer_data_with_array_buffer_view(
+                Gl::ARRAY_BUFFER,
+                &Float32Array::from(instance_data(sprites).as_slice()),
+                Gl::STREAM_DRAW,
+            );
+
+            gl.draw_arrays_instanced(Gl::TRIANGLE_STRIP, 0, 4, sprites.len() as i32);
+            gl.bind_vertex_array(None);
+        }
+
+        context.set_transform(1., 0., 0., 1., 0., 0.)?;
+        self.canvas.draw_onto(context)?;
+
+        Ok(())
+    }
+}
+
+/* Lay out the sprites in the instance buffer, in logical pixels and with
+ * colours between 0 and 1. */
+pub fn instance_data(sprites: &[Sprite]) -> Vec<f32> {
+    let mut data = Vec::with_capacity(sprites.len() * FLOATS_PER_SPRITE);
+
+    for sprite in sprites {
+        data.extend_from_slice(&[
+            sprite.from.x() as f32,
+            sprite.from.y() as f32,
+            sprite.to.x() as f32,
+            sprite.to.y() as f32,
+            sprite.radius as f32,
+            sprite.colour.0 as f32 / 255.,
+            sprite.colour.1 as f32 / 255.,
+            sprite.colour.2 as f32 / 255.,
+            sprite.alpha.clamp(0., 1.) as f32,
+        ]);
+    }
+
+    data
+}
+
+/* Get the size of a canvas with the given amount of pixels in logical
+ * pixels, and the size of one of its pixels in logical pixels. */
+fn view(width: u32, height: u32, pixel_ratio: f64) -> ([f32; 2], f32) {
+    (
+        [
+            (width as f64 / pixel_ratio) as f32,
+            (height as f64 / pixel_ratio) as f32,
+        ],
+        (1. / pixel_ratio) as f32,
+    )
+}
+
+fn compile_shader(gl: &Gl, kind: u32, source: &str) -> Result<WebGlShader, Error> {
+    let shader = gl.create_shader(kind).ok_or(Error::NoWebGl)?;
+    gl.shader_source(&shader, source);
+    gl.compile_shader(&shader);
+
+    if gl
+        .get_shader_parameter(&shader, Gl::COMPILE_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(shader)
+    } else {
+        Err(Error::Shader(
+            gl.get_shader_info_log(&shader).unwrap_or_default(),
+        ))
+    }
+}
+
+fn link_program(
+    gl: &Gl,
+    vertex_shader: &WebGlShader,
+    fragment_shader: &WebGlShader,
+) -> Result<WebGlProgram, Error> {
+    let program = gl.create_program().ok_or(Error::NoWebGl)?;
+    gl.attach_shader(&program, vertex_shader);
+    gl.attach_shader(&program, fragment_shader);
+    gl.link_program(&program);
+
+    if gl
+        .get_program_parameter(&program, Gl::LINK_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(program)
+    } else {
+        Err(Error::Shader(
+            gl.get_program_info_log(&program).unwrap_or_default(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::sim::TwoVec;
+    use super::*;
+
+    #[test]
+    fn sprites_are_laid_out_one_after_another() {
+        let sprites = [
+            Sprite::dot(TwoVec::new(10., 20.), 2.5, (255, 0, 51), 0.5),
+            Sprite::line(
+                TwoVec::new(1., 2.),
+                TwoVec::new(3., 4.),
+                1.,
+                (0, 255, 0),
+                3.,
+            ),
+        ];
+        let data = instance_data(&sprites);
+
+        assert_eq!(data.len(), sprites.len() * FLOATS_PER_SPRITE);
+        assert_eq!(
+            data[..FLOATS_PER_SPRITE],
+            [10., 20., 10., 20., 2.5, 1., 0., 0.2, 0.5]
+        );
+
+        /* Lines are given by their width, and alpha is clamped. */
+        assert_eq!(
+            data[FLOATS_PER_SPRITE..],
+            [1., 2., 3., 4., 0.5, 0., 1., 0., 1.]
+        );
+    }
+
+    #[test]
+    fn attributes_cover_every_float() {
+        let mut next = 0;
+
+        for (location, (_, size, offset)) in ATTRIBUTES.iter().enumerate() {
+            assert_eq!(ATTRIBUTES[location].0, location as u32);
+            assert_eq!(*offset, next);
+            next += size;
+        }
+
+        assert_eq!(next as usize, FLOATS_PER_SPRITE);
+    }
+
+    #[test]
+    fn the_view_is_in_logical_pixels() {
+        assert_eq!(view(800, 600, 1.), ([800., 600.], 1.));
+        assert_eq!(view(1600, 1200, 2.), ([800., 600.], 0.5));
+        assert_eq!(view(300, 150, 1.5), ([200., 100.], 1. / 1.5));
+    }
+}
@@ -0,0 +1,824 @@
+use js_sys::Date;
+use web_sys::CanvasRenderingContext2d;
+
+use super::beats::BeatScheduler;
+use super::camera::Camera;
+use super::colour;
+use super::detail::Detail;
+use super::fireworks::{
+    self, Burst, ColourShiftFirework, Firework, FireworkKind, FireworkState, SparkleFirework,
+    StandardFirework,
+};
+use super::flash;
+use super::landscape::Foreground;
+use super::random::Random;
+use super::render::Sprite;
+use super::sim::{Field, Fields, Particle, ThreeVec, TwoVec};
+use super::smoke::Smoke;
+use super::snapshot::{Snapshot, SNAPSHOT_VERSION};
+use super::sound::{SoundEvent, SoundKind};
+use super::water::Water;
+use crate::config::{Config, Settings};
+use crate::governor::Governor;
+use crate::replay::Event;
+
+const DEFAULT_MESSAGE: &str = "Gelukkig Nieuwjaar!";
+
+/* The largest width or height of a canvas browsers support. */
+const MAX_CANVAS_SIZE: u32 = 16384;
+
+/* The chance that a rocket whistles on its way up. */
+const WHISTLE_CHANCE: f64 = 0.3;
+
+/* The chance that a crackling explosion crackles in a single step. */
+const CRACKLE_CHANCE: f64 = 0.3;
+
+/* Everything which happens in a show, apart from the canvas it is drawn on.
+ * Sizes and positions are in logical pixels. */
+pub struct Scene {
+    width: u32,
+    height: u32,
+    stars: Vec<Particle>,
+    fireworks: Vec<Box<dyn Firework>>,
+    beat_fireworks: Vec<Box<dyn Firework>>,
+    beats: Option<BeatScheduler>,
+    smoke: Smoke,
+    fields: Fields,
+    camera: Camera,
+    foreground: Foreground,
+    water: Water,
+    sounds: Vec<SoundEvent>,
+    config: Config,
+    detail: Detail,
+    message: String,
+    countdown: Option<f64>,
+    random: Random,
+}
+
+impl Scene {
+    /* Create an empty show of the given size. */
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width: clamp_size(width),
+            height: clamp_size(height),
+            stars: Vec::new(),
+            fireworks: Vec::new(),
+            beat_fireworks: Vec::new(),
+            beats: None,
+            smoke: Smoke::new(),
+            fields: Fields::default(),
+            camera: Camera::default(),
+            foreground: Foreground::default(),
+            water: Water::default(),
+            sounds: Vec::new(),
+            config: Config::default(),
+            detail: Detail::FULL,
+            message: DEFAULT_MESSAGE.to_string(),
+            countdown: None,
+            random: Random::new(),
+        }
+    }
+
+    /* Change the size of the show, stretching everything in it along. Sizes
+     * are clamped to what a canvas can have. */
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let settings = self.settings();
+        let (width, height) = (clamp_size(width), clamp_size(height));
+        let width_ratio = width as f64 / self.width as f64;
+        let height_ratio = height as f64 / self.height as f64;
+
+        for star in &mut self.stars {
+            star.rescale(width_ratio, height_ratio);
+        }
+
+        for firework in self.fireworks.iter_mut().chain(&mut self.beat_fireworks) {
+            firework.rescale(&settings, width_ratio, height_ratio);
+        }
+
+        self.smoke.rescale(width_ratio, height_ratio);
+        self.fields.rescale(width_ratio, height_ratio);
+        self.foreground.rescale(width_ratio, height_ratio);
+
+        self.width = width;
+        self.height = height;
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /* Get the configuration of the show, changes to it apply immediately. */
+    pub fn config(&self) -> Config {
+        self.config.clone()
+    }
+
+    /* Get the settings at the current level of detail. */
+    pub fn settings(&self) -> Settings {
+        self.detail.apply(self.config.settings())
+    }
+
+    /* Change how much of the configured detail is drawn. */
+    pub fn set_detail(&mut self, detail: Detail) {
+        self.detail = detail;
+    }
+
+    /* The maximal amount of fireworks in the show. */
+    pub fn max_fireworks(&self) -> u32 {
+        self.settings().max_fireworks(self.width)
+    }
+
+    /* Set the message shown in the middle of the show. */
+    pub fn set_message(&mut self, message: &str) {
+        self.message = message.to_string();
+    }
+
+    /* Count down to the given time in milliseconds since the epoch before
+     * showing the message. */
+    pub fn set_countdown(&mut self, target: Option<f64>) {
+        self.countdown = target;
+    }
+
+    /* Restart the random numbers of the show from the given seed, so the
+     * show which follows is the same every time. */
+    pub fn seed(&mut self, seed: u64) {
+        self.random = Random::seeded(seed);
+    }
+
+    /* Replace the force fields which push the sparks around. */
+    pub fn set_fields(&mut self, fields: Vec<Field>) {
+        self.fields.set_fields(fields);
+    }
+
+    /* Move the pointer fields follow to the given logical position, or take
+     * it away. */
+    pub fn set_pointer(&mut self, x: f64, y: f64) {
+        self.fields.set_pointer(Some(TwoVec::new(x, y)));
+    }
+
+    pub fn clear_pointer(&mut self) {
+        self.fields.set_pointer(None);
+    }
+
+    /* Check whether any of the fields follows the pointer. */
+    pub fn follows_pointer(&self) -> bool {
+        self.fields.follows_pointer()
+    }
+
+    /* Spawn a firework, depending on what types of firework there are, as
+     * long as the show isn't full yet. */
+    pub fn spawn_firework(&mut self) {
+        let settings = self.settings();
+        if self.fireworks.len() as u32 >= settings.max_fireworks(self.width) {
+            return;
+        }
+
+        let kind = settings.firework_weights.pick(self.random.uniform());
+        let firework = self.create_firework(&settings, kind);
+        launch_sounds(&mut self.random, &mut self.sounds, firework.pos());
+        self.fireworks.push(firework);
+    }
+
+    /* Launch a single rocket from the given position on the bottom of the
+     * show, which only goes up once. */
+    pub fn launch_at(&mut self, x: f64) {
+        let settings = self.settings();
+        let kind = settings.firework_weights.pick(self.random.uniform());
+        let mut firework = self.create_firework(&settings, kind);
+        let vel = fireworks::random_vel(&mut self.random, self.height);
+        firework.launch(
+            &settings,
+            &mut self.random,
+            x.clamp(0., self.width as f64),
+            self.width,
+            self.height,
+            vel,
+        );
+
+        launch_sounds(&mut self.random, &mut self.sounds, firework.pos());
+        self.beat_fireworks.push(firework);
+    }
+
+    /* Launch rockets on the beats at the given times in seconds, replacing
+     * any beats which weren't launched yet. */
+    pub fn set_beats(&mut self, times: &[f64]) {
+        self.beats = Some(BeatScheduler::new(&mut self.random, times));
+    }
+
+    /* Keep the beats in sync with music which is at the given time. */
+    pub fn sync_beats(&mut self, time: f64) {
+        if let Some(beats) = &mut self.beats {
+            beats.sync(time);
+        }
+    }
+
+    /* Let an event from outside change the show. Settings which are out of
+     * range are ignored. */
+    pub fn apply(&mut self, event: &Event) {
+        match event {
+            Event::Spawn => self.spawn_firework(),
+            Event::Click { x } => self.launch_at(*x),
+            Event::Config { settings } => {
+                let _ = self.config.update(|current| *current = *settings);
+            }
+            Event::Resize { width, height } => self.resize(*width, *height),
+            Event::Detail { level } => self.set_detail(Governor::level_detail(*level)),
+            Event::Beats { times } => self.set_beats(times),
+            Event::SyncBeats { time } => self.sync_beats(*time),
+            Event::Fields { fields } => self.set_fields(fields.clone()),
+            Event::Pointer { x, y } => self.set_pointer(*x, *y),
+            Event::PointerLeave => self.clear_pointer(),
+        }
+    }
+
+    /* Get the sprites of the show in logical pixels, and the bursts which
+     * light it up. */
+    pub fn sprites(&self, settings: &Settings) -> (Vec<Sprite>, Vec<Burst>) {
+        let mut sprites = Vec::new();
+
+        /* Draw the stars. */
+        self.draw_stars(settings, &mut sprites);
+
+        /* Draw the smoke behind the fireworks, lit up by the explosions. */
+        let mut scene = Vec::new();
+        let bursts: Vec<Burst> = self
+            .fireworks
+            .iter()
+            .chain(&self.beat_fireworks)
+            .filter_map(|firework| firework.burst(settings))
+            .collect();
+        self.smoke.draw(&mut scene, &bursts);
+
+        /* Draw the fireworks. */
+        for firework in self.fireworks.iter().chain(&self.beat_fireworks) {
+            firework.draw(settings, &mut scene);
+        }
+
+        /* In 3D the scene is seen through the camera, in front of the stars
+         * which are too far away to move. */
+        if settings.three_d {
+            let (width, height) = (self.width, self.height);
+            sprites.extend(
+                scene
+                    .iter()
+                    .filter_map(|sprite| self.camera.project(sprite, width, height)),
+            );
+        } else {
+            sprites.append(&mut scene);
+        }
+
+        if let Some(water_line) = settings.water_line(self.height) {
+            self.water
+                .reflect(&mut sprites, water_line, self.height as f64);
+        }
+
+        (sprites, bursts)
+    }
+
+    /* Draw the light of the bursts on the sky behind the fireworks, and the
+     * landscape and the text on top, in logical pixels. */
+    pub fn draw_overlay(
+        &self,
+        context: &CanvasRenderingContext2d,
+        settings: &Settings,
+        bursts: &[Burst],
+    ) {
+        let (width, height) = (self.width as f64, self.height as f64);
+
+        if settings.sky_flash {
+            let flashes: Vec<Sprite> = bursts
+                .iter()
+                .map(flash::flash)
+                .filter_map(|flash| {
+                    if settings.three_d {
+                        self.camera.project(&flash, self.width, self.height)
+                    } else {
+                        Some(flash)
+                    }
+                })
+                .collect();
+            let _ = flash::draw_flashes(context, &flashes);
+        }
+
+        let water_line = settings.water_line(self.height);
+        if let Some(water_line) = water_line {
+            self.water.draw(context, water_line, width, height);
+        }
+
+        let ground = water_line.unwrap_or(height);
+        let bursts = self.seen_bursts(settings, bursts);
+        let _ = self.foreground.draw(context, height, ground, &bursts);
+
+        context.set_fill_style_str("yellow");
+        context.set_text_baseline("middle");
+        context.set_text_align("center");
+        context.set_font("70px sans-serif");
+
+        let text = match self.countdown {
+            Some(target) if target > Date::now() => countdown_text(target - Date::now()),
+            _ => self.message.clone(),
+        };
+
+        let _ = context.fill_text(&text, width / 2., height / 2.);
+    }
+
+    /* Place the bursts where they are seen on the canvas. In 3D bursts
+     * behind the camera are left out. */
+    fn seen_bursts(&self, settings: &Settings, bursts: &[Burst]) -> Vec<Burst> {
+        bursts
+            .iter()
+            .filter_map(|burst| {
+                let pos = if settings.three_d {
+                    let (pos, _) = self
+                        .camera
+                        .project_pos(&burst.pos, self.width, self.height)?;
+                    ThreeVec::from(pos)
+                } else {
+                    burst.pos
+                };
+
+                Some(Burst { pos, ..*burst })
+            })
+            .collect()
+    }
+
+    /* Simulate the fireworks. */
+    pub fn step(&mut self) {
+        let settings = self.settings();
+        let (width, height) = (self.width, self.height);
+        let mut launches = Vec::new();
+
+        /* Follow changes to the configuration, this also creates the stars
+         * in the first step. */
+        self.update_stars(settings.star_count);
+        if self.foreground.landscape() != settings.landscape {
+            self.foreground =
+                Foreground::generate(&mut self.random, settings.landscape, width, height);
+        }
+
+        /* When fewer fireworks fit in the show, for instance at a lower
+         * level of detail, the surplus burn out instead of starting over. */
+        let max_fireworks = settings.max_fireworks(width) as usize;
+        let mut count = self.fireworks.len();
+        let (random, fields, smoke, sounds) = (
+            &mut self.random,
+            &self.fields,
+            &mut self.smoke,
+            &mut self.sounds,
+        );
+        self.fireworks.retain_mut(|firework| {
+            if !step_firework(
+                firework.as_mut(),
+                &settings,
+                random,
+                fields,
+                width,
+                height,
+                smoke,
+                sounds,
+            ) {
+                return true;
+            }
+
+            if count > max_fireworks {
+                count -= 1;
+                return false;
+            }
+
+            launches.push(firework.pos());
+            true
+        });
+
+        /* Rockets launched on a beat or by hand only go up once. */
+        self.beat_fireworks.retain_mut(|firework| {
+            !step_firework(
+                firework.as_mut(),
+                &settings,
+                random,
+                fields,
+                width,
+                height,
+                smoke,
+                sounds,
+            )
+        });
+
+        let flights = match &mut self.beats {
+            Some(beats) => {
+                let (min_flight, max_flight) = fireworks::flight_range(&settings, height);
+                beats.step(min_flight, max_flight)
+            }
+            None => Vec::new(),
+        };
+
+        for steps in flights {
+            let kind = settings.firework_weights.pick(self.random.uniform());
+            let mut firework = self.create_firework(&settings, kind);
+            let x = self.random.uniform() * width as f64;
+            firework.launch(
+                &settings,
+                &mut self.random,
+                x,
+                width,
+                height,
+                fireworks::launch_vel(&settings, steps),
+            );
+
+            launches.push(firework.pos());
+            self.beat_fireworks.push(firework);
+        }
+
+        if self.beats.as_ref().is_some_and(BeatScheduler::finished) {
+            self.beats = None;
+        }
+
+        for pos in launches {
+            launch_sounds(&mut self.random, &mut self.sounds, pos);
+        }
+
+        self.smoke.step(settings.integrator);
+
+        if settings.three_d {
+            self.camera.orbit(settings.orbit_speed);
+        }
+
+        if settings.reflection {
+            self.water.step();
+        }
+    }
+
+    /* Save the complete state of the show. */
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            width: self.width,
+            height: self.height,
+            settings: self.config.settings(),
+            random: self.random.state(),
+            stars: self.stars.clone(),
+            fireworks: self
+                .fireworks
+                .iter()
+                .map(|firework| firework.state())
+                .collect(),
+            beat_fireworks: self
+                .beat_fireworks
+                .iter()
+                .map(|firework| firework.state())
+                .collect(),
+            beats: self.beats.clone(),
+            smoke: self.smoke.clone(),
+            fields: self.fields.clone(),
+            camera: self.camera,
+            foreground: self.foreground.clone(),
+            water: self.water,
+            message: self.message.clone(),
+            countdown: self.countdown,
+        }
+    }
+
+    /* Continue the show from a snapshot. A snapshot of a show of another
+     * size is stretched to fit this one. */
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), String> {
+        let (width, height) = (self.width, self.height);
+
+        self.restore_exactly(snapshot)?;
+        self.resize(width, height);
+
+        Ok(())
+    }
+
+    /* Continue the show from a snapshot, at the size of the show it was
+     * taken from. */
+    pub fn restore_exactly(&mut self, snapshot: Snapshot) -> Result<(), String> {
+        let settings = snapshot.settings;
+        self.config
+            .update(|current| *current = settings)
+            .map_err(|err| err.as_string().unwrap_or_default())?;
+
+        self.random = Random::from_state(snapshot.random);
+
+        self.stars = snapshot.stars;
+        self.fireworks = snapshot
+            .fireworks
+            .into_iter()
+            .map(FireworkState::restore)
+            .collect();
+        self.beat_fireworks = snapshot
+            .beat_fireworks
+            .into_iter()
+            .map(FireworkState::restore)
+            .collect();
+        self.beats = snapshot.beats;
+        self.smoke = snapshot.smoke;
+        self.fields = snapshot.fields;
+        self.camera = snapshot.camera;
+        self.foreground = snapshot.foreground;
+        self.water = snapshot.water;
+        self.sounds.clear();
+        self.message = snapshot.message;
+        self.countdown = snapshot.countdown;
+
+        self.width = clamp_size(snapshot.width);
+        self.height = clamp_size(snapshot.height);
+
+        Ok(())
+    }
+
+    /* Take the sounds made since the last time this was called, placed
+     * where they are seen on the canvas. In 3D sounds behind the camera
+     * stay where they were made. */
+    pub fn take_sounds(&mut self) -> Vec<SoundEvent> {
+        let mut sounds = std::mem::take(&mut self.sounds);
+
+        if self.settings().three_d {
+            for sound in &mut sounds {
+                if let Some((pos, _)) = self.camera.project_pos(&sound.pos, self.width, self.height)
+                {
+                    sound.pos = ThreeVec::from(pos);
+                }
+            }
+        }
+
+        sounds
+    }
+
+    /* Create a firework of the given type. */
+    fn create_firework(&mut self, settings: &Settings, kind: FireworkKind) -> Box<dyn Firework> {
+        let (width, height, random) = (self.width, self.height, &mut self.random);
+
+        match kind {
+            FireworkKind::Standard => {
+                Box::from(StandardFirework::new(settings, random, width, height))
+            }
+            FireworkKind::ColourShift => {
+                Box::from(ColourShiftFirework::new(settings, random, width, height))
+            }
+            FireworkKind::Sparkle => {
+                Box::from(SparkleFirework::new(settings, random, width, height))
+            }
+        }
+    }
+
+    /* Create stars at random positions in the show. */
+    fn create_stars(&mut self, count: u32, canvas_width: u32, canvas_height: u32) {
+        for _ in 0..count {
+            let pos = Particle::new(
+                TwoVec::new(
+                    self.random.uniform() * canvas_width as f64,
+                    self.random.uniform() * canvas_height as f64,
+                ),
+                TwoVec::zero(),
+            );
+
+            self.stars.push(pos);
+        }
+    }
+
+    /* Add or remove stars until there are the given amount. */
+    fn update_stars(&mut self, count: u32) {
+        let missing = count.saturating_sub(self.stars.len() as u32);
+
+        self.stars.truncate(count as usize);
+        self.create_stars(missing, self.width, self.height);
+    }
+
+    /* This function draws the stars on the canvas. */
+    fn draw_stars(&self, settings: &Settings, sprites: &mut Vec<Sprite>) {
+        for star in &self.stars {
+            sprites.push(star.sprite(colour::YELLOW, 1., settings.star_radius));
+        }
+    }
+}
+
+/* Make the sounds of a rocket launched from the given position. */
+fn launch_sounds(random: &mut Random, sounds: &mut Vec<SoundEvent>, pos: ThreeVec) {
+    sounds.push(SoundEvent::new(SoundKind::Launch, pos, 1.));
+
+    if random.uniform() < WHISTLE_CHANCE {
+        sounds.push(SoundEvent::new(SoundKind::Whistle, pos, 1.));
+    }
+}
+
+/* Simulate one step of a firework, leaving smoke and making sounds. Returns
+ * whether the explosion burnt out and a new rocket was launched. */
+#[allow(clippy::too_many_arguments)]
+fn step_firework(
+    firework: &mut dyn Firework,
+    settings: &Settings,
+    random: &mut Random,
+    fields: &Fields,
+    width: u32,
+    height: u32,
+    smoke: &mut Smoke,
+    sounds: &mut Vec<SoundEvent>,
+) -> bool {
+    let exploded = firework.burst(settings).is_some();
+
+    firework.step(settings, random, fields, width, height);
+
+    match (exploded, firework.burst(settings)) {
+        /* The firework has just exploded, so leave smoke behind. */
+        (false, Some(burst)) => {
+            smoke.puff(random, &burst.pos);
+            sounds.push(SoundEvent::new(SoundKind::Burst, burst.pos, 1.));
+        }
+        (true, Some(burst)) => {
+            if firework.crackles() && random.uniform() < CRACKLE_CHANCE {
+                let spread = 60. * random.uniform();
+                sounds.push(SoundEvent::new(
+                    SoundKind::Crackle,
+                    burst.pos + ThreeVec::from(TwoVec::random(random, spread)),
+                    burst.intensity,
+                ));
+            }
+        }
+        (true, None) => return true,
+        (false, None) => {}
+    }
+
+    false
+}
+
+/* Clamp a size of the canvas to at least a pixel, and at most the size
+ * browsers allow. */
+pub fn clamp_size(size: u32) -> u32 {
+    size.clamp(1, MAX_CANVAS_SIZE)
+}
+
+/* Format the remaining time of a countdown in milliseconds, like 1:02:03,
+ * 2:03 or just 3 in the last minute. */
+fn countdown_text(remaining: f64) -> String {
+    let seconds = (remaining / 1000.).ceil() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}:{:02}", minutes, seconds)
+    } else {
+        seconds.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::beats::STEPS_PER_SECOND;
+    use super::*;
+
+    const WIDTH: u32 = 800;
+    const HEIGHT: u32 = 600;
+
+    /* Launch a firework so it explodes after the given amount of steps, and
+     * step it until it has burnt out. Returns the sounds made in every
+     * step, after the sounds of the launch. */
+    fn listen(
+        firework: &mut dyn Firework,
+        settings: &Settings,
+        random: &mut Random,
+        flight: u64,
+    ) -> (Vec<SoundEvent>, Vec<Vec<SoundEvent>>) {
+        firework.launch(
+            settings,
+            random,
+            100.,
+            WIDTH,
+            HEIGHT,
+            fireworks::launch_vel(settings, flight),
+        );
+
+        let mut launch = Vec::new();
+        launch_sounds(random, &mut launch, firework.pos());
+
+        let mut steps = Vec::new();
+        let mut smoke = Smoke::new();
+        loop {
+            let mut sounds = Vec::new();
+            let relaunched = step_firework(
+                firework,
+                settings,
+                random,
+                &Fields::default(),
+                WIDTH,
+                HEIGHT,
+                &mut smoke,
+                &mut sounds,
+            );
+            steps.push(sounds);
+
+            if relaunched {
+                return (launch, steps);
+            }
+        }
+    }
+
+    #[test]
+    fn launches_and_bursts_are_heard() {
+        let settings = Settings::default();
+        let mut random = Random::seeded(3);
+        let mut firework = StandardFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+
+        let (launch, steps) = listen(&mut firework, &settings, &mut random, 40);
+
+        assert_eq!(launch[0].kind, SoundKind::Launch);
+        assert_eq!(launch[0].pos.xy(), TwoVec::new(100., HEIGHT as f64));
+        assert_eq!(launch[0].intensity, 1.);
+        assert!(launch[1..]
+            .iter()
+            .all(|sound| sound.kind == SoundKind::Whistle));
+
+        /* The burst is heard in the step the rocket explodes, and then the
+         * explosion burns quietly. */
+        assert!(steps[..39].iter().all(Vec::is_empty));
+        let burst = &steps[39][0];
+        assert_eq!((steps[39].len(), burst.kind), (1, SoundKind::Burst));
+        assert_eq!(burst.intensity, 1.);
+        assert_eq!(burst.pos.x(), 100.);
+        assert!(burst.pos.y() < HEIGHT as f64 - 100.);
+        assert!(steps[40..].iter().all(Vec::is_empty));
+        assert_eq!(steps.len() as u32, 40 + settings.particle_lifetime);
+    }
+
+    #[test]
+    fn sparkles_crackle_while_they_burn() {
+        let settings = Settings::default();
+        let mut random = Random::seeded(5);
+        let mut firework = SparkleFirework::new(&settings, &mut random, WIDTH, HEIGHT);
+
+        let (_, steps) = listen(&mut firework, &settings, &mut random, 40);
+        let burst = steps[39][0].pos;
+
+        let crackles: Vec<(usize, &SoundEvent)> = steps
+            .iter()
+            .enumerate()
+            .flat_map(|(step, sounds)| sounds.iter().map(move |sound| (step, sound)))
+            .filter(|(_, sound)| sound.kind == SoundKind::Crackle)
+            .collect();
+        assert!(crackles.len() > 10);
+
+        /* Crackles are heard around the burst, fading along with it. */
+        for (step, crackle) in &crackles {
+            let remaining = settings.particle_lifetime - (*step as u32 - 39);
+            let intensity = remaining as f64 / settings.particle_lifetime as f64;
+
+            assert!(*step > 39);
+            assert!((crackle.pos - burst).length() <= 60.);
+            assert!((crackle.intensity - intensity).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn sounds_are_heard_where_the_camera_shows_them() {
+        let mut scene = Scene::new(WIDTH, HEIGHT);
+        let behind = ThreeVec::from_plane(TwoVec::new(400., 300.), 200.);
+        let sound = || SoundEvent::new(SoundKind::Burst, behind, 1.);
+
+        /* Flat, the depth is ignored. */
+        scene.sounds.push(sound());
+        assert_eq!(scene.take_sounds()[0].pos, behind);
+
+        /* Looking from the side, a sound behind the middle of the canvas is
+         * heard beside it. */
+        scene
+            .config()
+            .update(|settings| settings.three_d = true)
+            .unwrap();
+        for _ in 0..STEPS_PER_SECOND as u32 {
+            scene.camera.orbit(90.);
+        }
+        scene.sounds.push(sound());
+
+        let heard = scene.take_sounds()[0].pos;
+        assert!((heard.x() - 600.).abs() < 1e-9);
+        assert_eq!((heard.y(), heard.z()), (300., 0.));
+    }
+
+    #[test]
+    fn bursts_light_the_landscape_where_the_camera_shows_them() {
+        let mut scene = Scene::new(WIDTH, HEIGHT);
+        let bursts = [Burst {
+            pos: ThreeVec::from_plane(TwoVec::new(400., 300.), 200.),
+            colour: colour::YELLOW,
+            intensity: 1.,
+        }];
+
+        let settings = Settings {
+            three_d: true,
+            ..Settings::default()
+        };
+        for _ in 0..STEPS_PER_SECOND as u32 {
+            scene.camera.orbit(90.);
+        }
+
+        let seen = scene.seen_bursts(&settings, &bursts);
+        assert!((seen[0].pos.x() - 600.).abs() < 1e-9);
+        assert_eq!((seen[0].pos.y(), seen[0].pos.z()), (300., 0.));
+        assert_eq!(
+            scene.seen_bursts(&Settings::default(), &bursts)[0].pos,
+            bursts[0].pos
+        );
+    }
+}
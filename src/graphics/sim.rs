@@ -1,14 +1,19 @@
 use std::f64;
-use std::ops::{Add, AddAssign, Sub};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
-use js_sys::Math;
-use web_sys::CanvasRenderingContext2d;
+use serde::{Deserialize, Serialize};
 
 use super::colour::Colour;
+use super::random::Random;
+use super::render::Sprite;
 
-#[derive(Clone, Copy)]
+/* A vector in the plane, which is used for positions, velocities and forces.
+ * Operators work on both vectors and references to them. */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub struct TwoVec {
+    #[serde(with = "crate::graphics::float")]
     x: f64,
+    #[serde(with = "crate::graphics::float")]
     y: f64,
 }
 
@@ -18,11 +23,15 @@ impl TwoVec {
         Self { x, y }
     }
 
-    /* Create a vector with random direction and given magnitude. */
-    pub fn random(magnitude: f64) -> Self {
-        let phi = Math::random() * f64::consts::TAU;
+    /* Create a vector with given length, pointing at the given angle in
+     * radians from the x axis. */
+    pub fn from_polar(length: f64, angle: f64) -> Self {
+        Self::new(angle.cos() * length, angle.sin() * length)
+    }
 
-        Self::new(phi.cos() * magnitude, phi.sin() * magnitude)
+    /* Create a vector with random direction and given magnitude. */
+    pub fn random(random: &mut Random, magnitude: f64) -> Self {
+        Self::from_polar(magnitude, random.uniform() * f64::consts::TAU)
     }
 
     /* Get the x coordinate. */
@@ -39,141 +48,1289 @@ impl TwoVec {
     pub const fn zero() -> Self {
         Self { x: 0., y: 0. }
     }
+
+    /* Get the length of the vector. */
+    pub fn length(&self) -> f64 {
+        self.x.hypot(self.y)
+    }
 }
 
-impl Add for &TwoVec {
-    type Output = TwoVec;
+/* Products, lengths and transformations of vectors. */
+impl TwoVec {
+    pub fn dot(&self, other: &TwoVec) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /* The z coordinate of the cross product of the vectors in space, which
+     * is positive when other is clockwise from self on the canvas. */
+    pub fn cross(&self, other: &TwoVec) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    /* Get the squared length, which is cheaper than the length. */
+    pub fn length_squared(&self) -> f64 {
+        self.dot(self)
+    }
+
+    /* Get the vector of length one in the same direction, or the zero vector
+     * if it has no direction. */
+    pub fn normalize(&self) -> Self {
+        let length = self.length();
 
-    fn add(self, rhs: Self) -> TwoVec {
-        TwoVec {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
+        if length > 0. {
+            self / length
+        } else {
+            Self::zero()
         }
     }
+
+    /* Rotate the vector by the given angle in radians, which is clockwise on
+     * the canvas because y points down. */
+    pub fn rotate(&self, angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+
+        Self::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+
+    /* Interpolate linearly, going from self at t = 0 to other at t = 1. */
+    pub fn lerp(&self, other: &TwoVec, t: f64) -> Self {
+        self + (other - self) * t
+    }
+}
+
+/* A vector in space, with the depth behind the canvas as the z coordinate.
+ * The simulation happens in space, while force fields, the ground and the
+ * walls only look at the plane of the canvas. Missing depths are read as
+ * zero, so vectors in the plane can be read as well. */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ThreeVec {
+    #[serde(with = "crate::graphics::float")]
+    x: f64,
+    #[serde(with = "crate::graphics::float")]
+    y: f64,
+    #[serde(default, with = "crate::graphics::float")]
+    z: f64,
+}
+
+impl ThreeVec {
+    /* Create ThreeVec with given x, y and z coordinates. */
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    /* Create a vector at the given depth behind a point on the canvas. */
+    pub const fn from_plane(vec: TwoVec, z: f64) -> Self {
+        Self::new(vec.x, vec.y, z)
+    }
+
+    /* Create a vector with random direction and given magnitude, spread
+     * evenly over a sphere. */
+    pub fn random(random: &mut Random, magnitude: f64) -> Self {
+        let z = 2. * random.uniform() - 1.;
+        let plane = TwoVec::random(random, (1. - z * z).sqrt());
+
+        Self::from_plane(plane, z) * magnitude
+    }
+
+    pub const fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub const fn y(&self) -> f64 {
+        self.y
+    }
+
+    /* Get the depth behind the canvas. */
+    pub const fn z(&self) -> f64 {
+        self.z
+    }
+
+    /* Get the vector on the canvas, leaving out the depth. */
+    pub const fn xy(&self) -> TwoVec {
+        TwoVec::new(self.x, self.y)
+    }
+
+    pub const fn zero() -> Self {
+        Self::new(0., 0., 0.)
+    }
+
+    pub fn length(&self) -> f64 {
+        self.length_squared().sqrt()
+    }
 }
 
-impl AddAssign<&TwoVec> for TwoVec {
-    fn add_assign(&mut self, rhs: &TwoVec) {
-        *self = &*self + &rhs;
+/* Products, lengths and transformations of vectors in space. */
+impl ThreeVec {
+    pub fn dot(&self, other: &ThreeVec) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: &ThreeVec) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length_squared(&self) -> f64 {
+        self.dot(self)
+    }
+
+    /* Get a vector of length one in the same direction. The zero vector has
+     * no direction, and stays zero. */
+    pub fn normalize(&self) -> Self {
+        let length = self.length();
+        if length == 0. {
+            return Self::zero();
+        }
+
+        self / length
+    }
+
+    /* Rotate the vector around the vertical axis by the given angle in
+     * radians, turning the x axis towards the z axis. */
+    pub fn rotate_y(&self, angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(
+            self.x * cos - self.z * sin,
+            self.y,
+            self.x * sin + self.z * cos,
+        )
+    }
+
+    pub fn lerp(&self, other: &ThreeVec, t: f64) -> Self {
+        self + (other - self) * t
     }
 }
 
-impl Sub for TwoVec {
-    type Output = Self;
+/* Implement an operator between two vectors for all combinations of vectors
+ * and references, and its assigning version. */
+macro_rules! vector_op {
+    ($vec:ident { $($field:ident),* }, $op:ident, $method:ident, $op_assign:ident, $method_assign:ident) => {
+        impl $op for $vec {
+            type Output = $vec;
 
-    fn sub(self, rhs: Self) -> Self {
-        Self {
-            x: self.x - rhs.x,
-            y: self.y - rhs.y,
+            fn $method(self, rhs: $vec) -> $vec {
+                $vec {
+                    $($field: self.$field.$method(rhs.$field)),*
+                }
+            }
+        }
+
+        impl $op<&$vec> for $vec {
+            type Output = $vec;
+
+            fn $method(self, rhs: &$vec) -> $vec {
+                self.$method(*rhs)
+            }
+        }
+
+        impl $op<$vec> for &$vec {
+            type Output = $vec;
+
+            fn $method(self, rhs: $vec) -> $vec {
+                (*self).$method(rhs)
+            }
+        }
+
+        impl $op<&$vec> for &$vec {
+            type Output = $vec;
+
+            fn $method(self, rhs: &$vec) -> $vec {
+                (*self).$method(*rhs)
+            }
+        }
+
+        impl $op_assign for $vec {
+            fn $method_assign(&mut self, rhs: $vec) {
+                *self = (*self).$method(rhs);
+            }
+        }
+
+        impl $op_assign<&$vec> for $vec {
+            fn $method_assign(&mut self, rhs: &$vec) {
+                *self = (*self).$method(*rhs);
+            }
+        }
+    };
+}
+
+/* Implement an operator between a vector and a number in the same way. */
+macro_rules! scalar_op {
+    ($vec:ident { $($field:ident),* }, $op:ident, $method:ident, $op_assign:ident, $method_assign:ident) => {
+        impl $op<f64> for $vec {
+            type Output = $vec;
+
+            fn $method(self, rhs: f64) -> $vec {
+                $vec {
+                    $($field: self.$field.$method(rhs)),*
+                }
+            }
+        }
+
+        impl $op<f64> for &$vec {
+            type Output = $vec;
+
+            fn $method(self, rhs: f64) -> $vec {
+                (*self).$method(rhs)
+            }
+        }
+
+        impl $op_assign<f64> for $vec {
+            fn $method_assign(&mut self, rhs: f64) {
+                *self = (*self).$method(rhs);
+            }
+        }
+    };
+}
+
+/* Implement multiplying a number by a vector, and negating a vector. */
+macro_rules! vector_ops {
+    ($vec:ident { $($field:ident),* }) => {
+        vector_op!($vec { $($field),* }, Add, add, AddAssign, add_assign);
+        vector_op!($vec { $($field),* }, Sub, sub, SubAssign, sub_assign);
+        scalar_op!($vec { $($field),* }, Mul, mul, MulAssign, mul_assign);
+        scalar_op!($vec { $($field),* }, Div, div, DivAssign, div_assign);
+
+        impl Mul<$vec> for f64 {
+            type Output = $vec;
+
+            fn mul(self, rhs: $vec) -> $vec {
+                rhs * self
+            }
+        }
+
+        impl Mul<&$vec> for f64 {
+            type Output = $vec;
+
+            fn mul(self, rhs: &$vec) -> $vec {
+                rhs * self
+            }
+        }
+
+        impl Neg for $vec {
+            type Output = $vec;
+
+            fn neg(self) -> $vec {
+                $vec {
+                    $($field: -self.$field),*
+                }
+            }
+        }
+
+        impl Neg for &$vec {
+            type Output = $vec;
+
+            fn neg(self) -> $vec {
+                -*self
+            }
+        }
+    };
+}
+
+vector_ops!(TwoVec { x, y });
+vector_ops!(ThreeVec { x, y, z });
+
+impl From<TwoVec> for (f64, f64) {
+    fn from(vec: TwoVec) -> Self {
+        (vec.x, vec.y)
+    }
+}
+
+impl From<&TwoVec> for (f64, f64) {
+    fn from(vec: &TwoVec) -> Self {
+        (vec.x, vec.y)
+    }
+}
+
+impl From<(f64, f64)> for TwoVec {
+    fn from((x, y): (f64, f64)) -> Self {
+        Self::new(x, y)
+    }
+}
+
+/* A vector in the plane of the canvas is a vector in space without depth. */
+impl From<TwoVec> for ThreeVec {
+    fn from(vec: TwoVec) -> Self {
+        Self::from_plane(vec, 0.)
+    }
+}
+
+impl From<&TwoVec> for ThreeVec {
+    fn from(vec: &TwoVec) -> Self {
+        Self::from_plane(*vec, 0.)
+    }
+}
+
+/* A way of moving a particle forward in time, under an acceleration which
+ * may depend on its position and velocity, like drag does. */
+pub trait Integrator {
+    fn integrate(
+        &self,
+        pos: &mut ThreeVec,
+        vel: &mut ThreeVec,
+        dt: f64,
+        acc: &dyn Fn(ThreeVec, ThreeVec) -> ThreeVec,
+    );
+}
+
+/* Change the velocity first and move with the new velocity. This is cheap
+ * and keeps orbits stable, but is only accurate to first order. */
+pub struct SemiImplicitEuler;
+
+impl Integrator for SemiImplicitEuler {
+    fn integrate(
+        &self,
+        pos: &mut ThreeVec,
+        vel: &mut ThreeVec,
+        dt: f64,
+        acc: &dyn Fn(ThreeVec, ThreeVec) -> ThreeVec,
+    ) {
+        *vel += acc(*pos, *vel) * dt;
+        *pos += *vel * dt;
+    }
+}
+
+/* Move along the parabola of the current acceleration, and change the
+ * velocity by the average of the accelerations at the start and the end.
+ * Velocity dependent accelerations are estimated from the velocity halfway. */
+pub struct VelocityVerlet;
+
+impl Integrator for VelocityVerlet {
+    fn integrate(
+        &self,
+        pos: &mut ThreeVec,
+        vel: &mut ThreeVec,
+        dt: f64,
+        acc: &dyn Fn(ThreeVec, ThreeVec) -> ThreeVec,
+    ) {
+        let start_acc = acc(*pos, *vel);
+        *pos += *vel * dt + start_acc * (dt * dt / 2.);
+
+        let half_vel = *vel + start_acc * (dt / 2.);
+        *vel = half_vel + acc(*pos, half_vel) * (dt / 2.);
+    }
+}
+
+/* The classic fourth order Runge-Kutta method, which samples the
+ * acceleration four times per step. It is the most accurate for strong
+ * drag, and the most expensive. */
+pub struct RungeKutta4;
+
+impl Integrator for RungeKutta4 {
+    fn integrate(
+        &self,
+        pos: &mut ThreeVec,
+        vel: &mut ThreeVec,
+        dt: f64,
+        acc: &dyn Fn(ThreeVec, ThreeVec) -> ThreeVec,
+    ) {
+        let (p1, v1) = (*pos, *vel);
+        let a1 = acc(p1, v1);
+
+        let (p2, v2) = (p1 + v1 * (dt / 2.), v1 + a1 * (dt / 2.));
+        let a2 = acc(p2, v2);
+
+        let (p3, v3) = (p1 + v2 * (dt / 2.), v1 + a2 * (dt / 2.));
+        let a3 = acc(p3, v3);
+
+        let v4 = v1 + a3 * dt;
+        let a4 = acc(p1 + v3 * dt, v4);
+
+        *pos += (v1 + v2 * 2. + v3 * 2. + v4) * (dt / 6.);
+        *vel += (a1 + a2 * 2. + a3 * 2. + a4) * (dt / 6.);
+    }
+}
+
+/* The integrators a show can be simulated with. */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegratorKind {
+    SemiImplicitEuler,
+    VelocityVerlet,
+    RungeKutta4,
+}
+
+impl IntegratorKind {
+    /* Look up an integrator by the name it has in a configuration. */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "semi_implicit_euler" => Some(IntegratorKind::SemiImplicitEuler),
+            "velocity_verlet" => Some(IntegratorKind::VelocityVerlet),
+            "runge_kutta4" => Some(IntegratorKind::RungeKutta4),
+            _ => None,
+        }
+    }
+
+    /* The name of the integrator in a configuration. */
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntegratorKind::SemiImplicitEuler => "semi_implicit_euler",
+            IntegratorKind::VelocityVerlet => "velocity_verlet",
+            IntegratorKind::RungeKutta4 => "runge_kutta4",
+        }
+    }
+
+    pub fn integrator(&self) -> &'static dyn Integrator {
+        match self {
+            IntegratorKind::SemiImplicitEuler => &SemiImplicitEuler,
+            IntegratorKind::VelocityVerlet => &VelocityVerlet,
+            IntegratorKind::RungeKutta4 => &RungeKutta4,
+        }
+    }
+}
+
+/* The ground, and optionally the sides, of the canvas, which particles
+ * bounce off. */
+pub struct Bounds {
+    pub width: f64,
+    pub height: f64,
+    pub walls: bool,
+    /* The fraction of the speed towards a surface which is kept when
+     * bouncing off it. */
+    pub restitution: f64,
+    /* The fraction of the speed along a surface which is lost when bouncing
+     * off it. */
+    pub friction: f64,
+}
+
+impl Bounds {
+    /* Bounce a particle which went through the ground or a wall back in.
+     * Movement in depth runs along both, so it only has friction. */
+    pub fn collide(&self, particle: &mut Particle) {
+        let (mut pos, mut vel) = (particle.pos, particle.vel);
+        let slide = 1. - self.friction;
+
+        if pos.y > self.height {
+            pos.y = self.height - (pos.y - self.height) * self.restitution;
+            vel = ThreeVec::new(vel.x * slide, -vel.y * self.restitution, vel.z * slide);
+        }
+
+        if self.walls {
+            if pos.x < 0. {
+                pos.x = -pos.x * self.restitution;
+                vel = ThreeVec::new(-vel.x * self.restitution, vel.y * slide, vel.z * slide);
+            } else if pos.x > self.width {
+                pos.x = self.width - (pos.x - self.width) * self.restitution;
+                vel = ThreeVec::new(-vel.x * self.restitution, vel.y * slide, vel.z * slide);
+            }
+        }
+
+        particle.pos = pos;
+        particle.vel = vel;
+    }
+
+    /* Check whether a particle can still be seen, or come back into view.
+     * Particles above the canvas fall back, but those beside it do not. */
+    pub fn contains(&self, particle: &Particle, margin: f64) -> bool {
+        (-margin..=self.width + margin).contains(&particle.pos.x)
+    }
+}
+
+/* The ways a force field can push particles. */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    /* Pulls particles towards its centre. */
+    Attractor,
+    /* Pushes particles away from its centre. */
+    Repulsor,
+    /* Spins particles around its centre, clockwise on the canvas. */
+    Vortex,
+    /* Pushes particles along swirls the size of its radius, everywhere. */
+    Flow,
+}
+
+impl FieldKind {
+    /* Look up a kind of field by the name it has in a configuration. */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "attractor" => Some(FieldKind::Attractor),
+            "repulsor" => Some(FieldKind::Repulsor),
+            "vortex" => Some(FieldKind::Vortex),
+            "flow" => Some(FieldKind::Flow),
+            _ => None,
         }
     }
 }
 
-impl Into<(f64, f64)> for &TwoVec {
-    fn into(self) -> (f64, f64) {
-        (self.x, self.y)
+/* A force field placed on the canvas. Except for flow fields, the force is
+ * strongest at the centre and fades out towards the radius. */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Field {
+    pub kind: FieldKind,
+    #[serde(flatten)]
+    pub pos: TwoVec,
+    pub strength: f64,
+    #[serde(default = "default_field_radius")]
+    pub radius: f64,
+    /* Whether the centre of the field is wherever the pointer is. */
+    #[serde(default)]
+    pub follow_pointer: bool,
+}
+
+fn default_field_radius() -> f64 {
+    200.
+}
+
+impl Field {
+    /* Create a field of the given kind which follows the pointer. */
+    pub fn pointer(kind: FieldKind) -> Self {
+        Self {
+            kind,
+            pos: TwoVec::zero(),
+            strength: 0.3,
+            radius: default_field_radius(),
+            follow_pointer: true,
+        }
+    }
+
+    /* Check that the field pushes the sparks around in a sensible way. */
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.pos.x.is_finite() && self.pos.y.is_finite()) {
+            return Err(format!(
+                "x and y of a field should be numbers, not {} and {}.",
+                self.pos.x, self.pos.y
+            ));
+        }
+
+        if !self.strength.is_finite() {
+            return Err(format!(
+                "strength of a field should be a number, not {}.",
+                self.strength
+            ));
+        }
+
+        if !(self.radius.is_finite() && self.radius > 0.) {
+            return Err(format!(
+                "radius of a field should be positive, not {}.",
+                self.radius
+            ));
+        }
+
+        Ok(())
+    }
+
+    /* Get the force of the field on a particle at the given position, with
+     * the field centred on the given point. */
+    fn force(&self, centre: &TwoVec, at: &TwoVec) -> TwoVec {
+        let diff = at - centre;
+
+        if self.kind == FieldKind::Flow {
+            let (x, y) = (diff.x / self.radius, diff.y / self.radius);
+            let angle = (x.sin() + (y + x.cos()).sin()) * f64::consts::PI;
+
+            return TwoVec::from_polar(self.strength, angle);
+        }
+
+        let distance = diff.length();
+        if distance >= self.radius {
+            return TwoVec::zero();
+        }
+
+        let away = diff.normalize() * (self.strength * (1. - distance / self.radius));
+        match self.kind {
+            FieldKind::Attractor => -away,
+            FieldKind::Repulsor => away,
+            FieldKind::Vortex => away.rotate(f64::consts::FRAC_PI_2),
+            FieldKind::Flow => unreachable!(),
+        }
     }
 }
 
-/* Convert an rgb triple and alpha value to a CSS colour. */
-fn rgba_to_colour(rgb: Colour, alpha: f64) -> String {
-    format!(
-        "rgba({},{},{},{})",
-        rgb.0,
-        rgb.1,
-        rgb.2,
-        alpha.max(0.).min(1.)
-    )
+/* The force fields on the canvas, and where the pointer is for those which
+ * follow it. */
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Fields {
+    fields: Vec<Field>,
+    pointer: Option<TwoVec>,
+}
+
+impl Fields {
+    /* Replace the fields. Without fields following the pointer, where it
+     * is no longer matters. */
+    pub fn set_fields(&mut self, fields: Vec<Field>) {
+        self.fields = fields;
+
+        if !self.follows_pointer() {
+            self.pointer = None;
+        }
+    }
+
+    /* Check whether any of the fields follows the pointer. */
+    pub fn follows_pointer(&self) -> bool {
+        self.fields.iter().any(|field| field.follow_pointer)
+    }
+
+    /* Move the pointer to the given position, or take it away when it left
+     * the canvas. */
+    pub fn set_pointer(&mut self, pointer: Option<TwoVec>) {
+        self.pointer = pointer;
+    }
+
+    /* Get the total force of all fields on a particle at the given
+     * position. Fields following the pointer do nothing without one. */
+    pub fn force(&self, at: &TwoVec) -> TwoVec {
+        let mut force = TwoVec::zero();
+
+        for field in &self.fields {
+            let centre = match (field.follow_pointer, &self.pointer) {
+                (false, _) => &field.pos,
+                (true, Some(pointer)) => pointer,
+                (true, None) => continue,
+            };
+
+            force += field.force(centre, at);
+        }
+
+        force
+    }
+
+    /* Stretch the fields along with the canvas. */
+    pub fn rescale(&mut self, x_scale: f64, y_scale: f64) {
+        for field in &mut self.fields {
+            field.pos = TwoVec::new(field.pos.x * x_scale, field.pos.y * y_scale);
+        }
+
+        if let Some(pointer) = &mut self.pointer {
+            *pointer = TwoVec::new(pointer.x * x_scale, pointer.y * y_scale);
+        }
+    }
 }
 
-/* This struct represents a particle with position, velocity and acceleration. */
+/* This struct represents a particle with position, velocity and acceleration
+ * in space. Particles created in the plane of the canvas stay in it, unless
+ * something pushes them out of it. */
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Particle {
-    pos: TwoVec,
-    vel: TwoVec,
-    acc: TwoVec,
+    pos: ThreeVec,
+    vel: ThreeVec,
+    acc: ThreeVec,
 }
 
 impl Particle {
-    /* Create a particle with given postion and velocity. */
+    /* Create a particle in the plane of the canvas with given postion and
+     * velocity. */
     pub const fn new(pos: TwoVec, vel: TwoVec) -> Self {
+        Self::in_space(ThreeVec::from_plane(pos, 0.), ThreeVec::from_plane(vel, 0.))
+    }
+
+    /* Create a particle with given position and velocity in space. */
+    pub const fn in_space(pos: ThreeVec, vel: ThreeVec) -> Self {
         Self {
             pos,
             vel,
-            acc: TwoVec::zero(),
+            acc: ThreeVec::zero(),
         }
     }
 
-    /* Create a particle at a given point with given speed and random velocity. */
-    pub fn random_at(pos: TwoVec, speed: f64) -> Particle {
-        Self {
-            pos: pos,
-            vel: TwoVec::random(speed),
-            acc: TwoVec::zero(),
-        }
+    /* Create a particle at a given point with given speed and random velocity
+     * along the canvas. */
+    pub fn random_at(random: &mut Random, pos: impl Into<ThreeVec>, speed: f64) -> Particle {
+        Self::in_space(pos.into(), TwoVec::random(random, speed).into())
+    }
+
+    /* Create a particle at a given point with given speed and random velocity
+     * in any direction in space. */
+    pub fn random_in_space(random: &mut Random, pos: ThreeVec, speed: f64) -> Particle {
+        Self::in_space(pos, ThreeVec::random(random, speed))
     }
 
     /* Apply a force to a particle. */
-    pub fn apply_force(&mut self, force: TwoVec) {
-        self.acc += &force;
+    pub fn apply_force(&mut self, force: impl Into<ThreeVec>) {
+        self.acc += force.into();
     }
 
-    /* Perform one step of a simulation and reset acceleration. */
-    pub fn step(&mut self) {
-        self.vel += &self.acc;
-        self.pos += &self.vel;
+    /* Move the particle forward by dt steps under the applied forces with
+     * the given integrator, and reset acceleration. */
+    pub fn step(&mut self, integrator: &dyn Integrator, dt: f64) {
+        self.integrate(integrator, dt, |_, _| ThreeVec::zero());
+    }
+
+    /* Move the particle forward by dt steps with the given integrator, under
+     * the applied forces and a force depending on its position and velocity.
+     * The applied forces are reset afterwards. */
+    pub fn integrate(
+        &mut self,
+        integrator: &dyn Integrator,
+        dt: f64,
+        force: impl Fn(ThreeVec, ThreeVec) -> ThreeVec,
+    ) {
+        let applied = self.acc;
+        integrator.integrate(&mut self.pos, &mut self.vel, dt, &|pos, vel| {
+            applied + force(pos, vel)
+        });
 
-        self.acc = TwoVec::zero();
+        self.acc = ThreeVec::zero();
     }
 
-    /* Draw the particle on a canvas with a given colour. */
-    pub fn draw(&self, context: &CanvasRenderingContext2d, colour: Colour, radius: f64) {
-        self.draw_rgba(context, colour, 1., radius);
+    /* Get a dot at the position of the particle. */
+    pub fn sprite(&self, colour: Colour, alpha: f64, radius: f64) -> Sprite {
+        Sprite::dot(self.pos.xy(), radius, colour, alpha).at_depth(self.pos.z, self.pos.z)
     }
 
-    /* Draw the particle on a canvas with a given colour and translucency. */
-    pub fn draw_rgba(
-        &self,
-        context: &CanvasRenderingContext2d,
-        colour: Colour,
-        alpha: f64,
-        radius: f64,
-    ) {
-        context.begin_path();
+    /* Get a line behind the particle as long as the given amount of steps of
+     * its movement. */
+    pub fn trail(&self, colour: Colour, alpha: f64, length: f64, width: f64) -> Sprite {
+        let end = self.pos - self.vel * length;
 
-        context.set_fill_style(&wasm_bindgen::JsValue::from_str(&rgba_to_colour(
-            colour, alpha,
-        )));
+        Sprite::line(self.pos.xy(), end.xy(), width, colour, alpha).at_depth(self.pos.z, end.z)
+    }
 
-        context
-            .arc(
-                self.pos.x(),
-                self.pos.y(),
-                radius,
-                0.,
-                std::f64::consts::TAU,
-            )
-            .unwrap();
+    /* Stretch the position and velocity of the particle by the given
+     * factors, for instance when the canvas is resized. Depth stretches along
+     * with the width. */
+    pub fn rescale(&mut self, x_scale: f64, y_scale: f64) {
+        let scale =
+            |vec: ThreeVec| ThreeVec::new(vec.x * x_scale, vec.y * y_scale, vec.z * x_scale);
 
-        context.fill();
+        self.pos = scale(self.pos);
+        self.vel = scale(self.vel);
     }
 
     /* Getter for the position of a particle. */
-    pub fn pos(&self) -> &TwoVec {
+    pub fn pos(&self) -> &ThreeVec {
         &self.pos
     }
 
     /* Setter for the position. */
-    pub fn set_pos(&mut self, new_pos: TwoVec) {
-        self.pos = new_pos;
+    pub fn set_pos(&mut self, new_pos: impl Into<ThreeVec>) {
+        self.pos = new_pos.into();
     }
 
     /* Getter for the velocity. */
-    pub fn vel(&self) -> &TwoVec {
+    pub fn vel(&self) -> &ThreeVec {
         &self.vel
     }
 
     /* Setter for the velocity. */
-    pub fn set_vel(&mut self, new_vel: TwoVec) {
-        self.vel = new_vel;
+    pub fn set_vel(&mut self, new_vel: impl Into<ThreeVec>) {
+        self.vel = new_vel.into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn assert_close(vec: &TwoVec, x: f64, y: f64) {
+        assert!(
+            (vec.x() - x).abs() < 1e-9 && (vec.y() - y).abs() < 1e-9,
+            "({}, {}) is not ({}, {})",
+            vec.x(),
+            vec.y(),
+            x,
+            y
+        );
+    }
+
+    #[test]
+    fn vectors_add_and_subtract() {
+        let a = TwoVec::new(1., 2.);
+        let b = TwoVec::new(-3., 0.5);
+
+        assert_close(&(a + b), -2., 2.5);
+        assert_close(&(a - b), 4., 1.5);
+
+        let mut c = a;
+        c += &b;
+        c += b;
+        assert_close(&c, -5., 3.);
+    }
+
+    #[test]
+    fn zero_is_neutral() {
+        let a = TwoVec::new(4., -7.);
+
+        assert_close(&(a + TwoVec::zero()), 4., -7.);
+        assert_close(&(a - a), 0., 0.);
+        assert_eq!(<(f64, f64)>::from(&a), (4., -7.));
+    }
+
+    #[test]
+    fn random_vectors_have_the_given_magnitude() {
+        let mut random = Random::new();
+        for magnitude in [0., 1., 2.5, 100.] {
+            let vec = TwoVec::random(&mut random, magnitude);
+
+            assert!((vec.x().hypot(vec.y()) - magnitude).abs() < 1e-9);
+        }
+    }
+
+    /* References are taken on purpose, to check that they work too. */
+    #[test]
+    #[allow(clippy::op_ref)]
+    fn operators_take_values_and_references() {
+        let a = TwoVec::new(3., -4.);
+        let b = TwoVec::new(1., 2.);
+
+        assert_eq!(a + b, &a + &b);
+        assert_eq!(a - &b, &a - b);
+        assert_eq!(a * 2., TwoVec::new(6., -8.));
+        assert_eq!(2. * &a, &a * 2.);
+        assert_eq!(a / 2., TwoVec::new(1.5, -2.));
+        assert_eq!(-a, TwoVec::new(-3., 4.));
+        assert_eq!(-&a + a, TwoVec::zero());
+
+        let mut c = a;
+        c -= b;
+        c *= 3.;
+        c /= 2.;
+        assert_eq!(c, TwoVec::new(3., -9.));
+    }
+
+    #[test]
+    fn products_and_lengths() {
+        let a = TwoVec::new(3., -4.);
+        let b = TwoVec::new(1., 2.);
+
+        assert_eq!(a.dot(&b), -5.);
+        assert_eq!(a.cross(&b), 10.);
+        assert_eq!(a.length(), 5.);
+        assert_eq!(a.length_squared(), 25.);
+        assert_close(&a.normalize(), 0.6, -0.8);
+        assert_eq!(TwoVec::zero().normalize(), TwoVec::zero());
+    }
+
+    #[test]
+    fn rotation_and_interpolation() {
+        let a = TwoVec::new(1., 0.);
+        let quarter = std::f64::consts::FRAC_PI_2;
+
+        assert_close(&a.rotate(quarter), 0., 1.);
+        assert_close(&TwoVec::from_polar(2., quarter), 0., 2.);
+        assert_close(&a.lerp(&TwoVec::new(3., 4.), 0.25), 1.5, 1.);
+        assert_eq!(TwoVec::from((1., 2.)), TwoVec::new(1., 2.));
+    }
+
+    #[test]
+    fn vectors_in_space() {
+        let a = ThreeVec::new(1., 2., 2.);
+        let b = ThreeVec::new(0., 1., 0.);
+
+        assert_eq!(a.length(), 3.);
+        assert_eq!(a.dot(&b), 2.);
+        assert_eq!(a.cross(&b), ThreeVec::new(-2., 0., 1.));
+        assert_eq!(a.cross(&b).dot(&a), 0.);
+        assert_eq!(-a + a * 2., a);
+        assert_eq!(a.xy(), TwoVec::new(1., 2.));
+        assert_eq!(
+            ThreeVec::from(TwoVec::new(1., 2.)),
+            ThreeVec::new(1., 2., 0.)
+        );
+        assert_eq!(ThreeVec::zero().normalize(), ThreeVec::zero());
+
+        /* A quarter turn around the vertical turns sideways into depth. */
+        let turned = ThreeVec::new(1., 5., 0.).rotate_y(std::f64::consts::FRAC_PI_2);
+        assert!((turned - ThreeVec::new(0., 5., 1.)).length() < 1e-9);
+    }
+
+    #[test]
+    fn random_directions_fill_the_sphere() {
+        let mut random = Random::new();
+        let directions: Vec<ThreeVec> = (0..1000)
+            .map(|_| ThreeVec::random(&mut random, 2.))
+            .collect();
+
+        for direction in &directions {
+            assert!((direction.length() - 2.).abs() < 1e-9);
+        }
+
+        /* Spread evenly, the directions average out near the centre. */
+        let mean = directions
+            .iter()
+            .fold(ThreeVec::zero(), |sum, vec| sum + vec)
+            / 1000.;
+        assert!(mean.length() < 0.2);
+    }
+
+    #[test]
+    fn vectors_without_depth_are_read() {
+        let vec: ThreeVec = serde_json::from_str(r#"{ "x": 1, "y": 2 }"#).unwrap();
+        assert_eq!(vec, ThreeVec::new(1., 2., 0.));
+    }
+
+    #[test]
+    fn step_integrates_velocity_then_position() {
+        let mut particle = Particle::new(TwoVec::new(10., 20.), TwoVec::new(1., -2.));
+        particle.apply_force(TwoVec::new(0.5, 0.));
+        particle.apply_force(TwoVec::new(0., 0.25));
+
+        particle.step(&SemiImplicitEuler, 1.);
+        assert_close(&particle.vel().xy(), 1.5, -1.75);
+        assert_close(&particle.pos().xy(), 11.5, 18.25);
+
+        /* The forces only last a single step. */
+        particle.step(&SemiImplicitEuler, 1.);
+        assert_close(&particle.vel().xy(), 1.5, -1.75);
+        assert_close(&particle.pos().xy(), 13., 16.5);
+
+        /* Longer steps go further. */
+        particle.step(&SemiImplicitEuler, 2.);
+        assert_close(&particle.pos().xy(), 16., 13.);
+    }
+
+    #[test]
+    fn step_under_gravity_follows_a_parabola() {
+        let gravity = 0.07;
+        let mut particle = Particle::new(TwoVec::zero(), TwoVec::new(0., -3.));
+
+        for _ in 0..50 {
+            particle.apply_force(TwoVec::new(0., gravity));
+            particle.step(&SemiImplicitEuler, 1.);
+        }
+
+        /* After n steps the position is n * v + n * (n + 1) / 2 * g. */
+        assert_close(&particle.vel().xy(), 0., -3. + 50. * gravity);
+        assert_close(&particle.pos().xy(), 0., -150. + 1275. * gravity);
+    }
+
+    const INTEGRATORS: [IntegratorKind; 3] = [
+        IntegratorKind::SemiImplicitEuler,
+        IntegratorKind::VelocityVerlet,
+        IntegratorKind::RungeKutta4,
+    ];
+
+    #[test]
+    fn integrators_are_found_by_name() {
+        for kind in INTEGRATORS {
+            assert_eq!(IntegratorKind::from_name(kind.name()), Some(kind));
+        }
+    }
+
+    /* Simulate drag slowing a particle down, and return how far the final
+     * velocity is from the exact one. */
+    fn drag_error(kind: IntegratorKind, drag: f64, dt: f64, steps: u32) -> f64 {
+        let mut particle = Particle::new(TwoVec::zero(), TwoVec::new(10., 0.));
+        for _ in 0..steps {
+            particle.integrate(kind.integrator(), dt, |_, vel| vel * -drag);
+        }
+
+        let exact = 10. * (-drag * dt * steps as f64).exp();
+        (particle.vel().x() - exact).abs()
+    }
+
+    #[test]
+    fn higher_orders_follow_drag_more_closely() {
+        let errors: Vec<f64> = INTEGRATORS
+            .iter()
+            .map(|&kind| drag_error(kind, 0.3, 1., 10))
+            .collect();
+
+        assert!(errors[0] > errors[1] && errors[1] > errors[2]);
+        assert!(errors[2] < 1e-3);
+    }
+
+    #[test]
+    fn runge_kutta_is_stable_under_strong_drag() {
+        /* With this much drag, Euler overshoots and the particle turns back
+         * every step. */
+        let mut euler = Particle::new(TwoVec::zero(), TwoVec::new(1., 0.));
+        let mut runge_kutta = euler.clone();
+
+        euler.integrate(&SemiImplicitEuler, 1., |_, vel| vel * -1.5);
+        runge_kutta.integrate(&RungeKutta4, 1., |_, vel| vel * -1.5);
+
+        assert!(euler.vel().x() < 0.);
+        assert!(runge_kutta.vel().x() > 0. && runge_kutta.vel().x() < 0.3);
+        assert!(drag_error(IntegratorKind::RungeKutta4, 1.5, 1., 20) < 1e-6);
+    }
+
+    #[test]
+    fn long_steps_under_drag_stay_bounded_with_higher_orders() {
+        /* For a force depending only on velocity, semi-implicit Euler is
+         * explicit Euler, which overshoots by more than it started with once
+         * drag * dt is over 2. */
+        let speed = |kind: IntegratorKind| {
+            let mut particle = Particle::new(TwoVec::zero(), TwoVec::new(10., 0.));
+            for _ in 0..50 {
+                particle.integrate(kind.integrator(), 2.5, |_, vel| vel * -1.);
+            }
+
+            particle.vel().length()
+        };
+
+        assert!(speed(IntegratorKind::SemiImplicitEuler) > 1e6);
+        assert!(speed(IntegratorKind::VelocityVerlet) < 1e-6);
+        assert!(speed(IntegratorKind::RungeKutta4) < 1e-6);
+    }
+
+    fn bounds(walls: bool) -> Bounds {
+        Bounds {
+            width: 100.,
+            height: 50.,
+            walls,
+            restitution: 0.5,
+            friction: 0.25,
+        }
+    }
+
+    #[test]
+    fn particles_bounce_off_the_ground() {
+        let mut particle = Particle::new(TwoVec::new(10., 54.), TwoVec::new(4., 6.));
+        bounds(false).collide(&mut particle);
+
+        assert_close(&particle.pos().xy(), 10., 48.);
+        assert_close(&particle.vel().xy(), 3., -3.);
+
+        /* Particles above the ground are left alone. */
+        bounds(false).collide(&mut particle);
+        assert_close(&particle.pos().xy(), 10., 48.);
+    }
+
+    #[test]
+    fn walls_are_optional() {
+        let mut particle = Particle::new(TwoVec::new(104., 20.), TwoVec::new(8., 4.));
+        bounds(false).collide(&mut particle);
+        assert_close(&particle.pos().xy(), 104., 20.);
+
+        bounds(true).collide(&mut particle);
+        assert_close(&particle.pos().xy(), 98., 20.);
+        assert_close(&particle.vel().xy(), -4., 3.);
+
+        let mut particle = Particle::new(TwoVec::new(-2., 20.), TwoVec::new(-8., 0.));
+        bounds(true).collide(&mut particle);
+        assert_close(&particle.pos().xy(), 1., 20.);
+        assert_close(&particle.vel().xy(), 4., 0.);
+    }
+
+    #[test]
+    fn particles_beside_the_canvas_are_gone() {
+        let at = |x, y| Particle::new(TwoVec::new(x, y), TwoVec::zero());
+
+        assert!(bounds(false).contains(&at(50., -1000.), 0.));
+        assert!(bounds(false).contains(&at(105., 20.), 10.));
+        assert!(!bounds(false).contains(&at(115., 20.), 10.));
+        assert!(!bounds(false).contains(&at(-11., 20.), 10.));
+    }
+
+    fn field(kind: FieldKind) -> Field {
+        Field {
+            kind,
+            pos: TwoVec::new(100., 100.),
+            strength: 1.,
+            radius: 50.,
+            follow_pointer: false,
+        }
+    }
+
+    fn fields(fields: Vec<Field>) -> Fields {
+        let mut all = Fields::default();
+        all.set_fields(fields);
+        all
+    }
+
+    #[test]
+    fn fields_push_towards_away_and_around() {
+        let at = TwoVec::new(125., 100.);
+
+        assert_close(
+            &fields(vec![field(FieldKind::Attractor)]).force(&at),
+            -0.5,
+            0.,
+        );
+        assert_close(
+            &fields(vec![field(FieldKind::Repulsor)]).force(&at),
+            0.5,
+            0.,
+        );
+        assert_close(&fields(vec![field(FieldKind::Vortex)]).force(&at), 0., 0.5);
+
+        /* Beyond the radius, and at the centre, there is no force. */
+        let all = fields(vec![field(FieldKind::Attractor), field(FieldKind::Vortex)]);
+        assert_eq!(all.force(&TwoVec::new(100., 150.)), TwoVec::zero());
+        assert_eq!(all.force(&TwoVec::new(100., 100.)), TwoVec::zero());
+    }
+
+    #[test]
+    fn flow_fields_push_everywhere() {
+        let flow = fields(vec![field(FieldKind::Flow)]);
+
+        for at in [TwoVec::zero(), TwoVec::new(1000., -300.)] {
+            assert!((flow.force(&at).length() - 1.).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn pointer_fields_follow_the_pointer() {
+        let mut all = fields(vec![Field::pointer(FieldKind::Repulsor)]);
+        let at = TwoVec::new(10., 10.);
+        assert_eq!(all.force(&at), TwoVec::zero());
+
+        all.set_pointer(Some(TwoVec::new(0., 10.)));
+        assert!(all.force(&at).x() > 0.);
+
+        all.rescale(2., 1.);
+        all.set_pointer(None);
+        assert_eq!(all.force(&at), TwoVec::zero());
+    }
+
+    #[test]
+    fn fields_are_read_from_json() {
+        let json = r#"[{ "kind": "vortex", "x": 400, "y": 300, "strength": 0.2 },
+            { "kind": "attractor", "x": 0, "y": 0, "strength": 1, "follow_pointer": true }]"#;
+        let read: Vec<Field> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(read[0].pos, TwoVec::new(400., 300.));
+        assert_eq!(read[0].radius, 200.);
+        assert!(!read[0].follow_pointer && read[1].follow_pointer);
+    }
+
+    #[test]
+    fn broken_fields_are_refused() {
+        assert!(field(FieldKind::Flow).validate().is_ok());
+
+        let broken = [
+            Field {
+                radius: 0.,
+                ..field(FieldKind::Attractor)
+            },
+            Field {
+                radius: -5.,
+                ..field(FieldKind::Flow)
+            },
+            Field {
+                strength: f64::NAN,
+                ..field(FieldKind::Vortex)
+            },
+            Field {
+                pos: TwoVec::new(f64::INFINITY, 0.),
+                ..field(FieldKind::Repulsor)
+            },
+            Field {
+                pos: TwoVec::new(0., f64::NAN),
+                ..field(FieldKind::Repulsor)
+            },
+        ];
+
+        for field in broken {
+            assert!(field.validate().is_err(), "{:?}", field);
+        }
+    }
+
+    #[test]
+    fn the_pointer_is_forgotten_without_fields_following_it() {
+        let mut all = fields(vec![Field::pointer(FieldKind::Vortex)]);
+        all.set_pointer(Some(TwoVec::new(5., 5.)));
+        assert!(all.follows_pointer());
+
+        all.set_fields(vec![field(FieldKind::Vortex)]);
+        assert!(!all.follows_pointer());
+        assert_eq!(all, fields(vec![field(FieldKind::Vortex)]));
+    }
+
+    #[test]
+    fn trails_point_against_the_velocity() {
+        let particle = Particle::new(TwoVec::new(5., 5.), TwoVec::new(1., -2.));
+        let trail = particle.trail((255, 0, 0), 1., 3., 2.);
+
+        assert_close(&trail.from, 5., 5.);
+        assert_close(&trail.to, 2., 11.);
+        assert_eq!(trail.radius, 1.);
+        assert!(!trail.is_dot());
+        assert!(particle.sprite((255, 0, 0), 1., 2.).is_dot());
+
+        let particle = Particle::in_space(ThreeVec::new(5., 5., 10.), ThreeVec::new(0., 0., 2.));
+        let trail = particle.trail((255, 0, 0), 1., 3., 2.);
+        assert_eq!((trail.from_depth, trail.to_depth), (10., 4.));
+    }
+
+    proptest! {
+        #[test]
+        fn particles_never_become_nan(
+            x in -1e4..1e4f64,
+            y in -1e4..1e4f64,
+            speed in 0. ..100f64,
+            forces in prop::collection::vec((-1. ..1f64, -1. ..1f64), 1..500),
+        ) {
+            let mut particle = Particle::random_at(&mut Random::new(), TwoVec::new(x, y), speed);
+
+            for (fx, fy) in forces {
+                particle.apply_force(TwoVec::new(fx, fy));
+                particle.step(&SemiImplicitEuler, 1.);
+
+                prop_assert!(particle.pos().x().is_finite() && particle.pos().y().is_finite());
+                prop_assert!(particle.vel().x().is_finite() && particle.vel().y().is_finite());
+            }
+        }
+
+        #[test]
+        fn rotation_keeps_length_and_angles(
+            x in -1e3..1e3f64,
+            y in -1e3..1e3f64,
+            angle in -10. ..10f64,
+        ) {
+            let vec = TwoVec::new(x, y);
+            let rotated = vec.rotate(angle);
+
+            prop_assert!((rotated.length() - vec.length()).abs() < 1e-9 * vec.length().max(1.));
+            prop_assert!((vec.dot(&rotated) - vec.length_squared() * angle.cos()).abs() < 1e-6 * vec.length_squared().max(1.));
+            prop_assert!((vec.cross(&rotated) - vec.length_squared() * angle.sin()).abs() < 1e-6 * vec.length_squared().max(1.));
+        }
+
+        #[test]
+        fn constant_forces_give_exact_parabolas(
+            ax in -1. ..1f64,
+            ay in -1. ..1f64,
+            vx in -10. ..10f64,
+            vy in -10. ..10f64,
+            vz in -10. ..10f64,
+            dt in 0.1..5f64,
+        ) {
+            let (acc, vel) = (ThreeVec::new(ax, ay, 0.), ThreeVec::new(vx, vy, vz));
+
+            for kind in [IntegratorKind::VelocityVerlet, IntegratorKind::RungeKutta4] {
+                let mut particle = Particle::in_space(ThreeVec::zero(), vel);
+                for _ in 0..10 {
+                    particle.apply_force(acc);
+                    particle.integrate(kind.integrator(), dt, |_, _| ThreeVec::zero());
+                }
+
+                let t = 10. * dt;
+                let pos = vel * t + acc * (t * t / 2.);
+                prop_assert!((particle.pos() - pos).length() < 1e-9 * pos.length().max(1.));
+                prop_assert!((particle.vel() - (vel + acc * t)).length() < 1e-9 * vel.length().max(1.));
+            }
+        }
+
+        #[test]
+        fn particles_stay_above_the_ground(
+            x in 0. ..100f64,
+            speed in 0. ..20f64,
+            restitution in 0. ..=1f64,
+            friction in 0. ..=1f64,
+        ) {
+            let bounds = Bounds { restitution, friction, ..bounds(true) };
+            let mut particle = Particle::random_at(&mut Random::new(), TwoVec::new(x, 25.), speed);
+
+            for _ in 0..200 {
+                particle.apply_force(TwoVec::new(0., 0.1));
+                particle.step(&SemiImplicitEuler, 1.);
+                bounds.collide(&mut particle);
+
+                prop_assert!(particle.pos().y() <= bounds.height);
+                prop_assert!((0. ..=bounds.width).contains(&particle.pos().x()));
+            }
+        }
+
+        #[test]
+        fn rescaling_is_reversible(
+            x in -1e4..1e4f64,
+            y in -1e4..1e4f64,
+            x_scale in 0.01..100f64,
+            y_scale in 0.01..100f64,
+        ) {
+            let mut particle = Particle::new(TwoVec::new(x, y), TwoVec::new(y / 100., x / 100.));
+            particle.rescale(x_scale, y_scale);
+            particle.rescale(1. / x_scale, 1. / y_scale);
+
+            prop_assert!((particle.pos().x() - x).abs() < 1e-6);
+            prop_assert!((particle.pos().y() - y).abs() < 1e-6);
+            prop_assert!((particle.vel().x() - y / 100.).abs() < 1e-6);
+            prop_assert!((particle.vel().y() - x / 100.).abs() < 1e-6);
+        }
     }
 }
@@ -0,0 +1,211 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use super::colour;
+use super::colour::Colour;
+use super::fireworks::Burst;
+use super::random::Random;
+use super::render::Sprite;
+use super::sim::{IntegratorKind, Particle, ThreeVec, TwoVec};
+
+/* The maximum amount of puffs alive at the same time. */
+const MAX_PUFFS: usize = 150;
+
+const PUFFS_PER_BURST: u32 = 6;
+
+const PUFF_LIFETIME: u32 = 600;
+
+/* How much the radius of a puff grows each step. */
+const PUFF_GROWTH: f64 = 0.08;
+
+/* How quickly a puff takes on the speed of the wind. */
+const PUFF_DRAG: f64 = 0.02;
+
+const SMOKE_COLOUR: Colour = (70, 70, 90);
+
+const SMOKE_ALPHA: f64 = 0.12;
+
+/* The distance from a burst at which it no longer lights up the smoke. */
+const TINT_RADIUS: f64 = 250.;
+
+pub const WIND: ThreeVec = ThreeVec::new(0.15, -0.02, 0.);
+
+/* A single cloud of smoke, slowly expanding and drifting with the wind. */
+#[derive(Clone, Serialize, Deserialize)]
+struct Puff {
+    particle: Particle,
+    #[serde(with = "crate::graphics::float")]
+    radius: f64,
+    lifetime: u32,
+}
+
+/* This struct represents the smoke left behind by all explosions. */
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Smoke {
+    puffs: VecDeque<Puff>,
+}
+
+impl Smoke {
+    /* Create a sky without any smoke. */
+    pub fn new() -> Self {
+        Self {
+            puffs: VecDeque::new(),
+        }
+    }
+
+    /* Leave a few puffs of smoke behind around the position of a burst. */
+    pub fn puff(&mut self, random: &mut Random, pos: &ThreeVec) {
+        for _ in 0..PUFFS_PER_BURST {
+            /* Make room by dropping the oldest puff. */
+            if self.puffs.len() >= MAX_PUFFS {
+                self.puffs.pop_front();
+            }
+
+            let spread = 30. * random.uniform();
+            let offset = ThreeVec::from(TwoVec::random(random, spread));
+            let speed = 0.3 * random.uniform();
+
+            self.puffs.push_back(Puff {
+                particle: Particle::random_at(random, pos + offset, speed),
+                radius: 10. + random.uniform() * 10.,
+                lifetime: PUFF_LIFETIME,
+            });
+        }
+    }
+
+    /* Let the smoke drift with the wind for one step. */
+    pub fn step(&mut self, integrator: IntegratorKind) {
+        for puff in &mut self.puffs {
+            puff.particle
+                .integrate(integrator.integrator(), 1., |_, vel| {
+                    (WIND - vel) * PUFF_DRAG
+                });
+
+            puff.radius += PUFF_GROWTH;
+            puff.lifetime -= 1;
+        }
+
+        self.puffs.retain(|puff| puff.lifetime > 0);
+    }
+
+    /* Stretch the smoke along with the canvas. */
+    pub fn rescale(&mut self, x_scale: f64, y_scale: f64) {
+        for puff in &mut self.puffs {
+            puff.particle.rescale(x_scale, y_scale);
+        }
+    }
+
+    /* Draw the smoke, lit up by the bursts close to it. */
+    pub fn draw(&self, sprites: &mut Vec<Sprite>, bursts: &[Burst]) {
+        for puff in &self.puffs {
+            let mut tint = SMOKE_COLOUR;
+
+            for burst in bursts {
+                let distance = (puff.particle.pos() - burst.pos).length();
+                let falloff = (1. - distance / TINT_RADIUS).max(0.);
+
+                tint = colour::colour_add(
+                    &tint,
+                    &colour::colour_mul(&burst.colour, burst.intensity * falloff),
+                );
+            }
+
+            let alpha = SMOKE_ALPHA * puff.lifetime as f64 / PUFF_LIFETIME as f64;
+
+            sprites.push(puff.particle.sprite(tint, alpha, puff.radius));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn puffed(bursts: &[TwoVec]) -> Smoke {
+        let mut random = Random::seeded(2);
+        let mut smoke = Smoke::new();
+
+        for burst in bursts {
+            smoke.puff(&mut random, &ThreeVec::from(*burst));
+        }
+
+        smoke
+    }
+
+    fn sprites(smoke: &Smoke, bursts: &[Burst]) -> Vec<Sprite> {
+        let mut sprites = Vec::new();
+        smoke.draw(&mut sprites, bursts);
+        sprites
+    }
+
+    #[test]
+    fn the_oldest_puffs_make_room() {
+        let far = TwoVec::new(1000., 1000.);
+        let mut bursts = vec![TwoVec::zero()];
+        bursts.extend(vec![far; MAX_PUFFS / PUFFS_PER_BURST as usize]);
+        let smoke = puffed(&bursts);
+
+        assert_eq!(smoke.puffs.len(), MAX_PUFFS);
+        assert!(smoke
+            .puffs
+            .iter()
+            .all(|puff| (puff.particle.pos().xy() - far).length() <= 30.));
+    }
+
+    #[test]
+    fn puffs_fade_and_vanish() {
+        let mut smoke = puffed(&[TwoVec::zero()]);
+        let alpha = |smoke: &Smoke| sprites(smoke, &[])[0].alpha;
+        assert_eq!(alpha(&smoke), SMOKE_ALPHA);
+
+        for _ in 0..PUFF_LIFETIME / 2 {
+            smoke.step(IntegratorKind::SemiImplicitEuler);
+        }
+        assert!((alpha(&smoke) - SMOKE_ALPHA / 2.).abs() < 1e-9);
+
+        for _ in 0..PUFF_LIFETIME / 2 {
+            smoke.step(IntegratorKind::SemiImplicitEuler);
+        }
+        assert!(smoke.puffs.is_empty());
+    }
+
+    #[test]
+    fn smoke_drifts_with_the_wind() {
+        let mut smoke = puffed(&[TwoVec::zero()]);
+        let start: Vec<ThreeVec> = smoke
+            .puffs
+            .iter()
+            .map(|puff| *puff.particle.pos())
+            .collect();
+
+        for _ in 0..400 {
+            smoke.step(IntegratorKind::SemiImplicitEuler);
+        }
+
+        for (puff, start) in smoke.puffs.iter().zip(start) {
+            assert!((puff.particle.vel() - WIND).length() < 0.01);
+            assert!(puff.particle.pos().x() > start.x());
+            assert!(puff.particle.pos().y() < start.y() + 30.);
+        }
+    }
+
+    #[test]
+    fn bursts_light_up_the_smoke_close_to_them() {
+        let smoke = puffed(&[TwoVec::zero()]);
+        let burst = |x| Burst {
+            pos: ThreeVec::new(x, 0., 0.),
+            colour: (255, 0, 0),
+            intensity: 1.,
+        };
+
+        for sprite in sprites(&smoke, &[burst(0.)]) {
+            assert!(sprite.colour.0 > SMOKE_COLOUR.0);
+            assert_eq!(sprite.colour.1, SMOKE_COLOUR.1);
+        }
+
+        for sprite in sprites(&smoke, &[burst(1000.)]) {
+            assert_eq!(sprite.colour, SMOKE_COLOUR);
+        }
+    }
+}
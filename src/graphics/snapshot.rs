@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::beats::BeatScheduler;
+use super::camera::Camera;
+use super::fireworks::FireworkState;
+use super::landscape::Foreground;
+use super::sim::{Fields, Particle};
+use super::smoke::Smoke;
+use super::water::Water;
+use crate::config::Settings;
+
+/* The version of the snapshot format, which changes whenever snapshots of
+ * the previous version can no longer be restored. */
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/* The complete state of a show at one moment, to continue it exactly from
+ * there later. Positions are in logical pixels on a canvas of the given
+ * size. */
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub width: u32,
+    pub height: u32,
+    pub settings: Settings,
+    /* The state of the random numbers. */
+    pub random: u64,
+    pub stars: Vec<Particle>,
+    pub fireworks: Vec<FireworkState>,
+    pub beat_fireworks: Vec<FireworkState>,
+    pub beats: Option<BeatScheduler>,
+    pub smoke: Smoke,
+    #[serde(default)]
+    pub fields: Fields,
+    #[serde(default)]
+    pub camera: Camera,
+    #[serde(default)]
+    pub foreground: Foreground,
+    #[serde(default)]
+    pub water: Water,
+    pub message: String,
+    #[serde(with = "crate::graphics::float::option")]
+    pub countdown: Option<f64>,
+}
+
+impl Snapshot {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /* Read a snapshot from JSON, refusing other versions of the format. */
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        match value.get("version").and_then(Value::as_u64) {
+            Some(version) if version == SNAPSHOT_VERSION as u64 => {}
+            Some(version) => {
+                return Err(format!(
+                    "Snapshots of version {} can't be restored, only of version {}.",
+                    version, SNAPSHOT_VERSION
+                ))
+            }
+            None => return Err("The snapshot has no version.".to_string()),
+        }
+
+        serde_json::from_value(value).map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::landscape::Landscape;
+    use super::super::scene::Scene;
+    use super::super::sim::TwoVec;
+    use super::*;
+
+    /* A show with a bit of everything going on. */
+    fn busy_scene() -> Scene {
+        let mut scene = Scene::new(800, 600);
+        scene.seed(11);
+        scene
+            .config()
+            .update(|settings| {
+                settings.three_d = true;
+                settings.reflection = true;
+                settings.landscape = Landscape::City;
+            })
+            .unwrap();
+        scene.set_beats(&[1., 1.5, 4.]);
+
+        for step in 0..150 {
+            if step % 20 == 0 {
+                scene.spawn_firework();
+                scene.launch_at(step as f64);
+            }
+
+            scene.step();
+        }
+
+        scene
+    }
+
+    #[test]
+    fn restored_shows_continue_the_same() {
+        let mut original = busy_scene();
+        let json = original.snapshot().to_json();
+
+        let mut restored = Scene::new(100, 100);
+        restored
+            .restore_exactly(Snapshot::from_json(&json).unwrap())
+            .unwrap();
+        assert_eq!(restored.snapshot().to_json(), json);
+
+        for _ in 0..300 {
+            original.step();
+            restored.step();
+        }
+
+        assert_eq!(restored.snapshot().to_json(), original.snapshot().to_json());
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let mut snapshot = busy_scene().snapshot();
+        snapshot.version = SNAPSHOT_VERSION + 1;
+
+        let err = Snapshot::from_json(&snapshot.to_json()).err().unwrap();
+        assert!(err.contains(&format!("version {}", SNAPSHOT_VERSION + 1)));
+        assert!(Snapshot::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn numbers_which_are_not_finite_are_kept() {
+        let mut snapshot = Scene::new(800, 600).snapshot();
+        snapshot.countdown = Some(f64::INFINITY);
+        snapshot.stars.push(Particle::new(
+            TwoVec::new(f64::NAN, f64::NEG_INFINITY),
+            TwoVec::zero(),
+        ));
+
+        let restored = Snapshot::from_json(&snapshot.to_json()).unwrap();
+        let star = restored.stars[0].pos();
+
+        assert_eq!(restored.countdown, Some(f64::INFINITY));
+        assert!(star.x().is_nan());
+        assert_eq!(star.y(), f64::NEG_INFINITY);
+    }
+}
@@ -0,0 +1,30 @@
+use super::sim::ThreeVec;
+
+/* The kinds of sounds the simulation makes. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SoundKind {
+    Launch,
+    Whistle,
+    Burst,
+    Crackle,
+}
+
+/* A sound made somewhere in the show, with an intensity between 0 and 1.
+ * These are only collected by the simulation, playing them is left to
+ * whoever drains them. */
+pub struct SoundEvent {
+    pub kind: SoundKind,
+    pub pos: ThreeVec,
+    pub intensity: f64,
+}
+
+impl SoundEvent {
+    /* Create a sound event of the given kind. */
+    pub const fn new(kind: SoundKind, pos: ThreeVec, intensity: f64) -> Self {
+        Self {
+            kind,
+            pos,
+            intensity,
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use js_sys::Object;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, OffscreenCanvas};
+
+use crate::error::Error;
+
+/* The canvas a show is drawn on: either an element on the page, or an
+ * OffscreenCanvas in a worker. */
+#[derive(Clone)]
+pub enum Surface {
+    Element(HtmlCanvasElement),
+    Offscreen(OffscreenCanvas),
+}
+
+impl Surface {
+    pub fn width(&self) -> u32 {
+        match self {
+            Surface::Element(canvas) => canvas.width(),
+            Surface::Offscreen(canvas) => canvas.width(),
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        match self {
+            Surface::Element(canvas) => canvas.height(),
+            Surface::Offscreen(canvas) => canvas.height(),
+        }
+    }
+
+    /* Change the amount of pixels of the canvas, which also clears it. */
+    pub fn set_size(&self, width: u32, height: u32) {
+        match self {
+            Surface::Element(canvas) => {
+                canvas.set_width(width);
+                canvas.set_height(height);
+            }
+            Surface::Offscreen(canvas) => {
+                canvas.set_width(width);
+                canvas.set_height(height);
+            }
+        }
+    }
+
+    /* Get the size the canvas is shown at on the page, which a worker
+     * doesn't know. */
+    pub fn client_size(&self) -> Option<(u32, u32)> {
+        match self {
+            Surface::Element(canvas) => Some((
+                canvas.client_width().max(0) as u32,
+                canvas.client_height().max(0) as u32,
+            )),
+            Surface::Offscreen(_) => None,
+        }
+    }
+
+    pub fn get_context(&self, kind: &str) -> Result<Option<Object>, JsValue> {
+        match self {
+            Surface::Element(canvas) => canvas.get_context(kind),
+            Surface::Offscreen(canvas) => canvas.get_context(kind),
+        }
+    }
+
+    /* Get the 2D context of the canvas. The 2D context of an OffscreenCanvas
+     * has the same methods as that of an element, so it is used as one. */
+    pub fn context_2d(&self) -> Result<CanvasRenderingContext2d, Error> {
+        let context = self.get_context("2d")?.ok_or(Error::NoContext)?;
+
+        match self {
+            Surface::Element(_) => context.dyn_into().map_err(|_| Error::NoContext),
+            Surface::Offscreen(_) => Ok(context.unchecked_into()),
+        }
+    }
+
+    /* Create a new canvas of the same kind, which isn't shown anywhere. */
+    pub fn create_sibling(&self) -> Result<Surface, Error> {
+        match self {
+            Surface::Element(canvas) => {
+                let document = canvas.owner_document().ok_or(Error::NoDocument)?;
+                let sibling = document
+                    .create_element("canvas")?
+                    .dyn_into::<HtmlCanvasElement>()
+                    .map_err(|_| Error::NoContext)?;
+
+                Ok(Surface::Element(sibling))
+            }
+            Surface::Offscreen(_) => Ok(Surface::Offscreen(OffscreenCanvas::new(1, 1)?)),
+        }
+    }
+
+    /* Copy the canvas onto a 2D context, with its top left corner at the
+     * origin. */
+    pub fn draw_onto(&self, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        match self {
+            Surface::Element(canvas) => context.draw_image_with_html_canvas_element(canvas, 0., 0.),
+            Surface::Offscreen(canvas) => context.draw_image_with_offscreen_canvas(canvas, 0., 0.),
+        }
+    }
+}
+
+impl From<HtmlCanvasElement> for Surface {
+    fn from(canvas: HtmlCanvasElement) -> Self {
+        Surface::Element(canvas)
+    }
+}
+
+impl From<OffscreenCanvas> for Surface {
+    fn from(canvas: OffscreenCanvas) -> Self {
+        Surface::Offscreen(canvas)
+    }
+}
@@ -0,0 +1,200 @@
+use std::f64::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+use web_sys::CanvasRenderingContext2d;
+
+use super::colour::{rgba_to_colour, Colour};
+use super::render::Sprite;
+use super::sim::TwoVec;
+
+/* How far ripples move reflections sideways, in logical pixels at the
+ * bottom of the canvas. Closer to the water line the water looks calmer. */
+const RIPPLE_AMPLITUDE: f64 = 6.;
+
+/* How many radians the ripples change per pixel below the water line, and
+ * per step. */
+const RIPPLE_FREQUENCY: f64 = 0.15;
+const RIPPLE_SPEED: f64 = 0.08;
+
+/* How much of its light a reflection keeps just below the water line. It
+ * fades out completely towards the bottom of the canvas. */
+const REFLECTION_ALPHA: f64 = 0.6;
+
+/* How far reflections are smeared up and down, in logical pixels at the
+ * bottom of the canvas. */
+const SMEAR: f64 = 10.;
+
+const WATER_COLOUR: Colour = (0, 8, 24);
+
+const WATER_ALPHA: f64 = 0.35;
+
+/* The water along the bottom of the canvas, which reflects the sky above
+ * it. The sky is squeezed into the water, so all of it is reflected. */
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Water {
+    /* How far the ripples have moved, in radians. */
+    #[serde(with = "crate::graphics::float")]
+    phase: f64,
+}
+
+impl Water {
+    /* Move the ripples for one step. */
+    pub fn step(&mut self) {
+        self.phase = (self.phase + RIPPLE_SPEED).rem_euclid(TAU);
+    }
+
+    /* Hide what is under the water at the given height, and add the
+     * reflections of the sprites above it on a canvas of the given height. */
+    pub fn reflect(&self, sprites: &mut Vec<Sprite>, water_line: f64, height: f64) {
+        sprites.retain_mut(|sprite| clip(sprite, water_line));
+
+        let reflections: Vec<Sprite> = sprites
+            .iter()
+            .filter_map(|sprite| self.reflection(sprite, water_line, height))
+            .collect();
+        sprites.extend(reflections);
+    }
+
+    /* Darken the water below the water line. The context should be
+     * transformed to logical pixels. */
+    pub fn draw(
+        &self,
+        context: &CanvasRenderingContext2d,
+        water_line: f64,
+        width: f64,
+        height: f64,
+    ) {
+        context.set_fill_style_str(&rgba_to_colour(WATER_COLOUR, WATER_ALPHA));
+        context.fill_rect(0., water_line, width, height - water_line);
+    }
+
+    /* Get the reflection of a sprite above the water line, if it is bright
+     * enough to see. */
+    fn reflection(&self, sprite: &Sprite, water_line: f64, height: f64) -> Option<Sprite> {
+        let depth = height - water_line;
+        if water_line <= 0. || depth <= 0. {
+            return None;
+        }
+
+        /* Mirror a point, and move it with the ripples. Returns how far it
+         * is down the water, from 0 at the water line to 1 at the bottom. */
+        let mirror = |pos: TwoVec| {
+            let below = (water_line - pos.y()) / water_line;
+            let y = water_line + below * depth;
+            let ripple = (below * depth * RIPPLE_FREQUENCY + self.phase).sin();
+
+            (
+                TwoVec::new(pos.x() + ripple * RIPPLE_AMPLITUDE * below, y),
+                below,
+            )
+        };
+
+        let (from, from_below) = mirror(sprite.from);
+        let (to, to_below) = mirror(sprite.to);
+        let below = from_below.min(to_below);
+
+        let alpha = sprite.alpha * REFLECTION_ALPHA * (1. - below);
+        if alpha <= 0. {
+            return None;
+        }
+
+        /* Smear the reflection along the water line, away from it. */
+        let (top, bottom) = if from.y() <= to.y() {
+            (from, to)
+        } else {
+            (to, from)
+        };
+        let smear = TwoVec::new(0., SMEAR * below);
+
+        Some(Sprite {
+            from: top - smear,
+            to: bottom + smear,
+            alpha,
+            ..*sprite
+        })
+    }
+}
+
+/* Cut off the part of a sprite under the water. Returns whether anything is
+ * left above it. */
+fn clip(sprite: &mut Sprite, water_line: f64) -> bool {
+    let (from, to) = (sprite.from, sprite.to);
+    let cut = |under: TwoVec, above: TwoVec| {
+        let t = (water_line - above.y()) / (under.y() - above.y());
+        above.lerp(&under, t)
+    };
+
+    match (from.y() > water_line, to.y() > water_line) {
+        (false, false) => true,
+        (true, true) => false,
+        (true, false) => {
+            sprite.from = cut(from, to);
+            true
+        }
+        (false, true) => {
+            sprite.to = cut(to, from);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot(x: f64, y: f64) -> Sprite {
+        Sprite::dot(TwoVec::new(x, y), 2., (255, 255, 255), 1.)
+    }
+
+    #[test]
+    fn the_sky_is_squeezed_into_the_water() {
+        let mut sprites = vec![dot(50., 150.), dot(50., 50.), dot(50., 220.)];
+        Water::default().reflect(&mut sprites, 200., 300.);
+
+        /* The dot under water is gone, the others are reflected. */
+        assert_eq!(sprites.len(), 4);
+
+        let near = &sprites[2];
+        assert!((near.from.y() - 225.).abs() < 3. && near.from.y() < near.to.y());
+        assert!((near.alpha - REFLECTION_ALPHA * 0.75).abs() < 1e-9);
+
+        let far = &sprites[3];
+        assert!((far.from.y() - 275.).abs() < 8.);
+        assert!(far.alpha < near.alpha);
+        assert!((far.from.x() - 50.).abs() <= RIPPLE_AMPLITUDE);
+    }
+
+    #[test]
+    fn the_top_of_the_sky_is_not_reflected() {
+        let mut sprites = vec![dot(50., 0.)];
+        Water::default().reflect(&mut sprites, 200., 300.);
+
+        assert_eq!(sprites.len(), 1);
+    }
+
+    #[test]
+    fn lines_into_the_water_are_cut_off() {
+        let mut sprite = Sprite::line(
+            TwoVec::new(0., 300.),
+            TwoVec::new(100., 100.),
+            2.,
+            (255, 255, 255),
+            1.,
+        );
+
+        assert!(clip(&mut sprite, 200.));
+        assert_eq!(sprite.from, TwoVec::new(50., 200.));
+        assert_eq!(sprite.to, TwoVec::new(100., 100.));
+    }
+
+    #[test]
+    fn ripples_move() {
+        let mut water = Water::default();
+        let before = water.reflection(&dot(50., 100.), 200., 300.).unwrap();
+        water.step();
+        let after = water.reflection(&dot(50., 100.), 200., 300.).unwrap();
+
+        assert_ne!(before.from.x(), after.from.x());
+        assert_eq!(before.from.y(), after.from.y());
+    }
+}
@@ -0,0 +1,51 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use wasm_bindgen::JsValue;
+
+use crate::graphics::Field;
+
+/* A command sent from the page to a show running in a worker, as a message
+ * like `{ type: "resize", width: 800, height: 600, pixel_ratio: 2 }`. */
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Command {
+    Spawn,
+    /* Launch a rocket from the given x position in CSS pixels. */
+    Click {
+        x: f64,
+    },
+    /* Change some of the settings, the others stay as they are. */
+    Config {
+        settings: Map<String, Value>,
+    },
+    /* The size of the canvas on the page in CSS pixels, and the
+     * devicePixelRatio of the window. */
+    Resize {
+        width: u32,
+        height: u32,
+        pixel_ratio: Option<f64>,
+    },
+    /* Replace the force fields, see FireworkShow::set_fields. */
+    Fields {
+        fields: Vec<Field>,
+    },
+    /* The pointer moved to the given position in CSS pixels. */
+    Pointer {
+        x: f64,
+        y: f64,
+    },
+    PointerLeave,
+    Pause,
+    Resume,
+}
+
+impl Command {
+    /* Read a command from the data of a message. */
+    pub fn parse(message: &JsValue) -> Result<Self, String> {
+        let json = js_sys::JSON::stringify(message)
+            .map(String::from)
+            .map_err(|_| "The message can't be read as JSON.".to_string())?;
+
+        serde_json::from_str(&json).map_err(|err| format!("Invalid message {}: {}", json, err))
+    }
+}
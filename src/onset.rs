@@ -0,0 +1,109 @@
+/* The amount of samples the energy is measured over. */
+const WINDOW_SIZE: usize = 1024;
+
+/* The amount of samples between two measurements. */
+const HOP_SIZE: usize = 512;
+
+/* The time in seconds of recent energy a window is compared against. */
+const HISTORY_SECONDS: f64 = 1.;
+
+/* How much louder than the recent past a window has to be to count as an
+ * onset. */
+const SENSITIVITY: f64 = 1.5;
+
+/* Windows quieter than this are never onsets. */
+const MIN_ENERGY: f64 = 1e-4;
+
+/* The minimal time in seconds between two onsets, rockets can't keep up
+ * with anything faster anyway. */
+const MIN_GAP: f64 = 0.25;
+
+/* Find the times in seconds at which notes start in mono PCM samples, by
+ * looking for sudden rises in energy. */
+pub fn detect_onsets(samples: &[f32], sample_rate: f32) -> Vec<f64> {
+    let sample_rate = sample_rate as f64;
+    if samples.len() < WINDOW_SIZE || sample_rate <= 0. {
+        return Vec::new();
+    }
+
+    let energies: Vec<f64> = (0..=(samples.len() - WINDOW_SIZE) / HOP_SIZE)
+        .map(|i| {
+            let window = &samples[i * HOP_SIZE..i * HOP_SIZE + WINDOW_SIZE];
+            window.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / WINDOW_SIZE as f64
+        })
+        .collect();
+
+    let history = ((HISTORY_SECONDS * sample_rate / HOP_SIZE as f64) as usize).max(1);
+    let mut onsets: Vec<f64> = Vec::new();
+
+    for (i, energy) in energies.iter().enumerate().skip(1) {
+        let recent = &energies[i.saturating_sub(history)..i];
+        let average = recent.iter().sum::<f64>() / recent.len() as f64;
+
+        if *energy < MIN_ENERGY || *energy < average * SENSITIVITY {
+            continue;
+        }
+
+        let time = (i * HOP_SIZE + WINDOW_SIZE / 2) as f64 / sample_rate;
+
+        match onsets.last() {
+            Some(last) if time - last < MIN_GAP => {}
+            _ => onsets.push(time),
+        }
+    }
+
+    onsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44100.;
+
+    /* Silence with short clicks of a decaying tone at the given times. */
+    fn clicks(times: &[f64], seconds: f64) -> Vec<f32> {
+        let mut samples = vec![0.; (seconds * SAMPLE_RATE as f64) as usize];
+
+        for time in times {
+            let start = (time * SAMPLE_RATE as f64) as usize;
+            for i in 0..2000 {
+                let t = i as f32 / SAMPLE_RATE;
+                samples[start + i] =
+                    0.8 * (-t * 60.).exp() * (t * 1000. * std::f32::consts::TAU).sin();
+            }
+        }
+
+        samples
+    }
+
+    #[test]
+    fn clicks_are_onsets() {
+        let times = [0.5, 1.25, 2., 2.6];
+        let onsets = detect_onsets(&clicks(&times, 3.), SAMPLE_RATE);
+
+        assert_eq!(onsets.len(), times.len());
+        for (onset, time) in onsets.iter().zip(&times) {
+            /* An onset is found within a hop of the click. */
+            assert!(
+                (onset - time).abs() < 2. * HOP_SIZE as f64 / SAMPLE_RATE as f64,
+                "{} is not {}",
+                onset,
+                time
+            );
+        }
+    }
+
+    #[test]
+    fn quick_clicks_are_one_onset() {
+        let onsets = detect_onsets(&clicks(&[1., 1.1], 2.), SAMPLE_RATE);
+
+        assert_eq!(onsets.len(), 1);
+    }
+
+    #[test]
+    fn silence_has_no_onsets() {
+        assert!(detect_onsets(&clicks(&[], 2.), SAMPLE_RATE).is_empty());
+        assert!(detect_onsets(&[0.8; 500], SAMPLE_RATE).is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+use crate::config::{FireworkWeights, Quality, Settings};
+use crate::graphics::{
+    Field, FieldKind, Graphics, IntegratorKind, Landscape, Palette, RendererKind,
+};
+
+/* Configure a show from the query string of the page, for instance
+ * `?message=Hallo&palette=gold&seed=42`. Returns a warning for every
+ * parameter which could not be used. */
+pub fn apply_query(query: &str, graphics: &mut Graphics) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    for (key, value) in parse_query(query, decode_uri_component) {
+        if let Err(err) = apply_parameter(&key, &value, graphics) {
+            warnings.push(format!("Ignoring {}={}: {}", key, value, err));
+        }
+    }
+
+    warnings
+}
+
+/* Split a query string into its keys and values, decoded with the given
+ * function. Parts which can't be decoded are kept as they are. */
+fn parse_query(query: &str, decode: impl Fn(&str) -> Option<String>) -> Vec<(String, String)> {
+    let decode = |part: &str| {
+        let part = part.replace('+', " ");
+        decode(&part).unwrap_or(part)
+    };
+
+    query
+        .trim_start_matches('?')
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+/* Decode a percent-encoded part of a query string. */
+fn decode_uri_component(part: &str) -> Option<String> {
+    js_sys::decode_uri_component(part).ok().map(String::from)
+}
+
+fn apply_parameter(key: &str, value: &str, graphics: &mut Graphics) -> Result<(), String> {
+    match key {
+        "message" => graphics.set_message(value),
+        "palette" => {
+            let palette = Palette::from_name(value)
+                .ok_or_else(|| "there is no palette with that name.".to_string())?;
+            update(graphics, |settings| settings.palette = palette)?;
+        }
+        /* The density is relative to the default amount of fireworks. */
+        "density" => {
+            let density = parse_number(value)?;
+            if density <= 0. {
+                return Err("the density should be positive.".to_string());
+            }
+
+            let spacing = Settings::default().firework_spacing / density;
+            update(graphics, |settings| settings.firework_spacing = spacing)?;
+        }
+        "seed" => {
+            let seed = value
+                .parse()
+                .map_err(|_| "the seed should be a whole number.".to_string())?;
+            graphics.seed(seed);
+        }
+        "weights" => {
+            let weights = parse_weights(value)?;
+            update(graphics, |settings| settings.firework_weights = weights)?;
+        }
+        /* The countdown target is either a date or milliseconds since the
+         * epoch. */
+        "countdown" => {
+            let target = match value.parse::<f64>() {
+                Ok(target) => target,
+                Err(_) => js_sys::Date::parse(value),
+            };
+            if !target.is_finite() {
+                return Err("the countdown should be a date.".to_string());
+            }
+
+            graphics.set_countdown(Some(target));
+        }
+        "quality" => {
+            let quality = Quality::from_name(value)
+                .ok_or_else(|| "the quality should be low, medium or high.".to_string())?;
+            update(graphics, |settings| settings.quality = quality)?;
+        }
+        "integrator" => {
+            let integrator = IntegratorKind::from_name(value).ok_or_else(|| {
+                "the integrator should be semi_implicit_euler, velocity_verlet or runge_kutta4."
+                    .to_string()
+            })?;
+            update(graphics, |settings| settings.integrator = integrator)?;
+        }
+        "landscape" => {
+            let landscape = Landscape::from_name(value).ok_or_else(|| {
+                "the landscape should be none, city, hills or horizon.".to_string()
+            })?;
+            update(graphics, |settings| settings.landscape = landscape)?;
+        }
+        /* Reflect the show in water, taking up the given fraction of the
+         * canvas if there is one. */
+        "reflection" => {
+            let level = match value {
+                "" => Settings::default().water_level,
+                level => parse_number(level)?,
+            };
+            update(graphics, |settings| {
+                settings.reflection = true;
+                settings.water_level = level;
+            })?;
+        }
+        /* Show the fireworks in 3D, with the camera orbiting at the given
+         * speed in degrees per second if there is one. */
+        "3d" => {
+            let speed = match value {
+                "" => Settings::default().orbit_speed,
+                speed => parse_number(speed)?,
+            };
+            update(graphics, |settings| {
+                settings.three_d = true;
+                settings.orbit_speed = speed;
+            })?;
+        }
+        /* A field which follows the pointer, to push the sparks around. */
+        "pointer" => {
+            let kind = FieldKind::from_name(value).ok_or_else(|| {
+                "the pointer should be attractor, repulsor, vortex or flow.".to_string()
+            })?;
+            graphics.set_fields(vec![Field::pointer(kind)]);
+        }
+        /* Without WebGL the show is drawn in 2d. */
+        "renderer" => {
+            let kind = RendererKind::from_name(value)
+                .ok_or_else(|| "the renderer should be 2d, webgl or software.".to_string())?;
+            graphics
+                .set_renderer(kind)
+                .map_err(|err| format!("{} Drawing in 2d instead.", err))?;
+        }
+        /* The page decides whether the show runs in a worker, and what a
+         * click does. */
+        "worker" | "launch_on_click" => {}
+        _ => return Err("unknown parameter.".to_string()),
+    }
+
+    Ok(())
+}
+
+/* Parse weights like `standard:2,sparkle:1`. Types which aren't mentioned
+ * keep their default weight. */
+fn parse_weights(value: &str) -> Result<FireworkWeights, String> {
+    let mut weights = FireworkWeights::default();
+
+    for pair in value.split(',') {
+        let (kind, weight) = pair
+            .split_once(':')
+            .ok_or_else(|| "weights should look like standard:2,sparkle:1.".to_string())?;
+        let weight = parse_number(weight)?;
+
+        match kind {
+            "standard" => weights.standard = weight,
+            "colour_shift" => weights.colour_shift = weight,
+            "sparkle" => weights.sparkle = weight,
+            _ => return Err(format!("there is no firework type called {}.", kind)),
+        }
+    }
+
+    Ok(weights)
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| format!("{} is not a number.", value))
+}
+
+/* Change the settings of a show, reporting invalid results. */
+fn update(graphics: &Graphics, change: impl FnOnce(&mut Settings)) -> Result<(), String> {
+    graphics
+        .config()
+        .update(change)
+        .map_err(|err| err.as_string().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Decode only what the tests need, without the browser. */
+    fn decode(part: &str) -> Option<String> {
+        (!part.contains("%%")).then(|| part.replace("%21", "!"))
+    }
+
+    fn pairs(query: &str) -> Vec<(String, String)> {
+        parse_query(query, decode)
+    }
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn queries_are_split_and_decoded() {
+        assert_eq!(
+            pairs("?message=Happy+new+year%21&3d&&seed=4"),
+            vec![
+                pair("message", "Happy new year!"),
+                pair("3d", ""),
+                pair("seed", "4")
+            ]
+        );
+        assert_eq!(pairs("message=100%%"), vec![pair("message", "100%%")]);
+        assert!(pairs("?").is_empty());
+    }
+
+    #[test]
+    fn duplicate_keys_are_kept_in_order() {
+        /* Applied in order, the last one wins. */
+        assert_eq!(
+            pairs("seed=1&seed=2"),
+            vec![pair("seed", "1"), pair("seed", "2")]
+        );
+    }
+
+    #[test]
+    fn weights_replace_the_defaults_they_mention() {
+        let weights = parse_weights("sparkle:2.5,standard:0").unwrap();
+
+        assert_eq!((weights.standard, weights.sparkle), (0., 2.5));
+        assert_eq!(
+            weights.colour_shift,
+            FireworkWeights::default().colour_shift
+        );
+        assert_eq!(parse_weights("standard:1,standard:3").unwrap().standard, 3.);
+    }
+
+    #[test]
+    fn malformed_weights_are_refused() {
+        assert_eq!(
+            parse_weights("standard=2").err().unwrap(),
+            "weights should look like standard:2,sparkle:1."
+        );
+        assert_eq!(
+            parse_weights("standard:2,").err().unwrap(),
+            "weights should look like standard:2,sparkle:1."
+        );
+        assert_eq!(
+            parse_weights("rocket:1").err().unwrap(),
+            "there is no firework type called rocket."
+        );
+        assert_eq!(
+            parse_weights("sparkle:lots").err().unwrap(),
+            "lots is not a number."
+        );
+        assert!(parse_weights("sparkle:inf").is_err());
+    }
+
+    #[test]
+    fn numbers_are_finite() {
+        assert_eq!(parse_number("-1.5e2"), Ok(-150.));
+        for value in &["NaN", "inf", "-infinity", "1e400", "", "1,5"] {
+            assert_eq!(
+                parse_number(value),
+                Err(format!("{} is not a number.", value))
+            );
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::Settings;
+use crate::graphics::{Field, Snapshot};
+
+/* The version of the recording format, which changes whenever recordings of
+ * the previous version can no longer be replayed. */
+const RECORDING_VERSION: u32 = 1;
+
+/* Something from outside the simulation which changes the course of a
+ * show. */
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Spawn,
+    /* A rocket launched by clicking at the given logical x position. */
+    Click { x: f64 },
+    Config { settings: Settings },
+    Resize { width: u32, height: u32 },
+    /* The level of detail chosen by the governor. */
+    Detail { level: usize },
+    Beats { times: Vec<f64> },
+    SyncBeats { time: f64 },
+    Fields { fields: Vec<Field> },
+    /* The pointer moved to the given logical position. */
+    Pointer { x: f64, y: f64 },
+    PointerLeave,
+}
+
+/* An event and the step before which it happened. */
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct Entry {
+    step: u64,
+    #[serde(flatten)]
+    event: Event,
+}
+
+/* A show from the moment recording started: its state at that moment, and
+ * everything which happened to it afterwards. With the same random numbers,
+ * this is all it takes to show it again. */
+#[derive(Serialize, Deserialize)]
+pub struct Recording {
+    version: u32,
+    start: Snapshot,
+    events: Vec<Entry>,
+}
+
+impl Recording {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /* Read a recording from JSON, refusing other versions of the format. */
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        match value.get("version").and_then(Value::as_u64) {
+            Some(version) if version == RECORDING_VERSION as u64 => {}
+            Some(version) => {
+                return Err(format!(
+                    "Recordings of version {} can't be replayed, only of version {}.",
+                    version, RECORDING_VERSION
+                ))
+            }
+            None => return Err("The recording has no version.".to_string()),
+        }
+
+        serde_json::from_value(value).map_err(|err| err.to_string())
+    }
+}
+
+/* This struct keeps a log of the events of a show while it is recorded. */
+pub struct Recorder {
+    recording: Recording,
+    settings: Settings,
+}
+
+impl Recorder {
+    /* Start recording a show from a snapshot of it, while it is drawn at the
+     * given level of detail. */
+    pub fn start(start: Snapshot, detail_level: usize) -> Self {
+        let settings = start.settings;
+
+        Self {
+            recording: Recording {
+                version: RECORDING_VERSION,
+                start,
+                events: vec![Entry {
+                    step: 0,
+                    event: Event::Detail {
+                        level: detail_level,
+                    },
+                }],
+            },
+            settings,
+        }
+    }
+
+    /* Log an event which happened before the given step since the start. */
+    pub fn record(&mut self, step: u64, event: Event) {
+        self.recording.events.push(Entry { step, event });
+    }
+
+    /* Log the settings if they changed since they were last logged. They
+     * can be changed from anywhere, so they are compared every step. */
+    pub fn record_settings(&mut self, step: u64, settings: Settings) {
+        if settings != self.settings {
+            self.settings = settings;
+            self.record(step, Event::Config { settings });
+        }
+    }
+
+    pub fn finish(self) -> Recording {
+        self.recording
+    }
+}
+
+/* This struct feeds the events of a recording back to a show. */
+pub struct Replayer {
+    events: VecDeque<Entry>,
+}
+
+impl Replayer {
+    /* Start replaying a recording. The show should first be restored to the
+     * returned snapshot. */
+    pub fn start(recording: Recording) -> (Snapshot, Self) {
+        (
+            recording.start,
+            Self {
+                events: recording.events.into(),
+            },
+        )
+    }
+
+    /* Take the events which happened before the given step since the
+     * start. */
+    pub fn due(&mut self, step: u64) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        while self.events.front().is_some_and(|entry| entry.step <= step) {
+            events.extend(self.events.pop_front().map(|entry| entry.event));
+        }
+
+        events
+    }
+
+    /* Check whether all events have been replayed. */
+    pub fn finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governor::Governor;
+    use crate::graphics::Scene;
+
+    fn kinds(events: &[Event]) -> Vec<String> {
+        events.iter().map(|event| format!("{:?}", event)).collect()
+    }
+
+    #[test]
+    fn events_are_due_in_order() {
+        let mut recorder = Recorder::start(Scene::new(800, 600).snapshot(), 0);
+        recorder.record(0, Event::Spawn);
+        recorder.record(3, Event::Click { x: 1. });
+        recorder.record(3, Event::Click { x: 2. });
+        recorder.record(5, Event::PointerLeave);
+
+        let (_, mut replayer) = Replayer::start(recorder.finish());
+
+        assert_eq!(
+            kinds(&replayer.due(0)),
+            kinds(&[Event::Detail { level: 0 }, Event::Spawn])
+        );
+        assert!(replayer.due(2).is_empty());
+        assert_eq!(
+            kinds(&replayer.due(4)),
+            kinds(&[Event::Click { x: 1. }, Event::Click { x: 2. }])
+        );
+        assert!(!replayer.finished());
+
+        /* Events which were missed are still replayed. */
+        assert_eq!(kinds(&replayer.due(10)), kinds(&[Event::PointerLeave]));
+        assert!(replayer.finished());
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let recording = Recorder::start(Scene::new(800, 600).snapshot(), 0).finish();
+        let mut json: Value = serde_json::from_str(&recording.to_json()).unwrap();
+        assert!(Recording::from_json(&json.to_string()).is_ok());
+
+        json["version"] = Value::from(RECORDING_VERSION + 1);
+        let err = Recording::from_json(&json.to_string()).err().unwrap();
+        assert!(err.contains(&format!("version {}", RECORDING_VERSION + 1)));
+
+        json.as_object_mut().unwrap().remove("version");
+        assert!(Recording::from_json(&json.to_string()).is_err());
+    }
+
+    #[test]
+    fn replays_end_the_same() {
+        let mut live = Scene::new(800, 600);
+        live.seed(5);
+        for _ in 0..50 {
+            live.step();
+        }
+
+        /* The live show is drawn in full detail. */
+        let full = Governor::new().max_level();
+        let mut recorder = Recorder::start(live.snapshot(), full);
+        let events = [
+            (0, Event::Spawn),
+            (10, Event::Click { x: 300. }),
+            (
+                10,
+                Event::Beats {
+                    times: vec![1., 2.],
+                },
+            ),
+            (
+                40,
+                Event::Resize {
+                    width: 640,
+                    height: 480,
+                },
+            ),
+            (60, Event::Detail { level: 2 }),
+            (80, Event::Click { x: 100. }),
+        ];
+        let mut next = events.iter().peekable();
+
+        for step in 0..400 {
+            while let Some((_, event)) = next.next_if(|(at, _)| *at == step) {
+                recorder.record(step, event.clone());
+                live.apply(event);
+            }
+
+            if step == 120 {
+                live.config()
+                    .update(|settings| settings.gravity *= 2.)
+                    .unwrap();
+            }
+
+            recorder.record_settings(step, live.config().settings());
+            live.step();
+        }
+
+        let recording = Recording::from_json(&recorder.finish().to_json()).unwrap();
+        let (start, mut replayer) = Replayer::start(recording);
+        let mut replay = Scene::new(100, 100);
+        replay.restore_exactly(start).unwrap();
+
+        for step in 0..400 {
+            for event in replayer.due(step) {
+                replay.apply(&event);
+            }
+
+            replay.step();
+        }
+
+        assert!(replayer.finished());
+        assert_eq!(replay.snapshot().to_json(), live.snapshot().to_json());
+    }
+}
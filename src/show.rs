@@ -0,0 +1,649 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlCanvasElement, OffscreenCanvas};
+
+use crate::animation::Animation;
+use crate::audio::Audio;
+use crate::config::Config;
+use crate::error::Error;
+use crate::governor::Governor;
+use crate::graphics::{Field, Graphics, RendererKind, Snapshot};
+use crate::message::Command;
+use crate::replay::{Event, Recorder, Recording, Replayer};
+use crate::{onset, query};
+
+/* The simulation and sound of a show, shared with its animation loop. After
+ * the show is destroyed there is no simulation anymore, and it does nothing.
+ * Everything which changes the course of the show passes through here as an
+ * event, so it can be recorded and replayed. */
+pub struct Stage {
+    graphics: Option<Graphics>,
+    audio: Audio,
+    governor: Governor,
+    /* The amount of steps simulated since recording or replaying started. */
+    steps: u64,
+    recorder: Option<Recorder>,
+    replayer: Option<Replayer>,
+}
+
+impl Stage {
+    fn new(graphics: Graphics) -> Self {
+        Self {
+            graphics: Some(graphics),
+            audio: Audio::new(),
+            governor: Governor::new(),
+            steps: 0,
+            recorder: None,
+            replayer: None,
+        }
+    }
+
+    /* Simulate one step. */
+    pub fn step(&mut self) {
+        if let Some(replayer) = &mut self.replayer {
+            for event in replayer.due(self.steps) {
+                self.apply(&event);
+            }
+        }
+
+        /* After the replay the governor takes over the detail again. */
+        if self.replayer.as_ref().is_some_and(Replayer::finished) {
+            self.replayer = None;
+            self.input(Event::Detail {
+                level: self.governor.level(),
+            });
+        }
+
+        if let Some(graphics) = &mut self.graphics {
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record_settings(self.steps, graphics.config().settings());
+            }
+
+            graphics.step();
+            self.steps += 1;
+        }
+    }
+
+    /* Draw the current state of the simulation, and play the sounds made
+     * since the last time it was drawn. */
+    pub fn draw(&mut self) {
+        if let Some(graphics) = &mut self.graphics {
+            graphics.draw();
+
+            /* Failing to play a sound is not worth stopping the show for. */
+            let sounds = graphics.take_sounds();
+            let _ = self
+                .audio
+                .play(&sounds, graphics.width() as f64, graphics.height() as f64);
+        }
+    }
+
+    /* Take the duration of a frame in milliseconds into account, lowering
+     * or raising the detail to keep up the frame rate. */
+    pub fn measure(&mut self, frame_time: f64) {
+        /* While replaying, the detail follows the recording. */
+        if self.replayer.is_some() {
+            return;
+        }
+
+        if self.governor.measure(frame_time) {
+            self.input(Event::Detail {
+                level: self.governor.level(),
+            });
+        }
+    }
+
+    /* Spawn a new firework. */
+    pub fn spawn(&mut self) {
+        self.input(Event::Spawn);
+    }
+
+    /* Change the size of the canvas in CSS pixels. */
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.input(Event::Resize { width, height });
+    }
+
+    /* Match the size of the canvas to the size it is shown at, with as many
+     * pixels as the screen has there. A canvas which isn't shown keeps its
+     * size, and a canvas in a worker is resized by the page. */
+    pub fn fit_canvas(&mut self) {
+        if let Some(graphics) = &mut self.graphics {
+            let (width, height) = match graphics.client_size() {
+                Some(size) => size,
+                None => return,
+            };
+
+            graphics.set_pixel_ratio(device_pixel_ratio());
+
+            if width > 0 && height > 0 && (width, height) != (graphics.width(), graphics.height()) {
+                self.resize(width, height);
+            }
+        }
+    }
+
+    /* The maximal amount of fireworks on the canvas. */
+    pub fn max_fireworks(&self) -> u32 {
+        match &self.graphics {
+            Some(graphics) => graphics.max_fireworks(),
+            None => 0,
+        }
+    }
+
+    /* Check whether any of the fields follows the pointer. */
+    fn follows_pointer(&self) -> bool {
+        self.graphics
+            .as_ref()
+            .is_some_and(Graphics::follows_pointer)
+    }
+
+    /* Let an event from outside change the show, and record it. While
+     * replaying, the show only follows the recording. */
+    fn input(&mut self, event: Event) {
+        if self.replayer.is_some() {
+            return;
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(self.steps, event.clone());
+        }
+
+        self.apply(&event);
+    }
+
+    fn apply(&mut self, event: &Event) {
+        if let Some(graphics) = &mut self.graphics {
+            graphics.apply(event);
+        }
+    }
+}
+
+/* A firework show on a canvas. Every show has its own simulation, so several
+ * shows can run on the same page. */
+#[wasm_bindgen]
+pub struct FireworkShow {
+    stage: Rc<RefCell<Stage>>,
+    animation: Option<Animation>,
+}
+
+impl FireworkShow {
+    fn from_graphics(graphics: Graphics) -> Self {
+        Self {
+            stage: Rc::new(RefCell::new(Stage::new(graphics))),
+            animation: None,
+        }
+    }
+
+    /* Replace the force fields, if they are all valid. */
+    fn replace_fields(&mut self, fields: Vec<Field>) -> Result<(), JsValue> {
+        for field in &fields {
+            field.validate().map_err(|err| JsValue::from_str(&err))?;
+        }
+
+        self.stage.borrow_mut().input(Event::Fields { fields });
+
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl FireworkShow {
+    /* Create a new show on the given canvas. */
+    #[wasm_bindgen(constructor)]
+    pub fn new(canvas: HtmlCanvasElement) -> Result<FireworkShow, JsValue> {
+        Ok(Self::from_graphics(Graphics::new(canvas.into())?))
+    }
+
+    /* Create a show in a worker, on a canvas the page transferred with
+     * transferControlToOffscreen. The page tells the show its size with
+     * resize messages. */
+    pub fn from_offscreen(
+        canvas: OffscreenCanvas,
+        pixel_ratio: f64,
+    ) -> Result<FireworkShow, JsValue> {
+        let mut graphics = Graphics::new(canvas.into())?;
+        graphics.set_pixel_ratio(pixel_ratio);
+
+        Ok(Self::from_graphics(graphics))
+    }
+
+    /* Create a show filling the window, on the canvas with the given id. If
+     * there is no such element, a canvas is added to the page. When the show
+     * can't be started, the reason is shown on the page instead. */
+    pub fn fill_window(id: &str) -> Result<FireworkShow, JsValue> {
+        match window_canvas(id).and_then(|canvas| Graphics::new(canvas.into())) {
+            Ok(mut graphics) => {
+                graphics.set_pixel_ratio(device_pixel_ratio());
+                Ok(Self::from_graphics(graphics))
+            }
+            Err(err) => {
+                report_error(&err);
+                Err(err.into())
+            }
+        }
+    }
+
+    /* Configure the show from a URL query string, such as
+     * `window.location.search`. Parameters which can't be used are reported
+     * to the console. This should happen before the first step, so the seed
+     * applies to the entire show. */
+    pub fn configure(&mut self, query: &str) {
+        if let Some(graphics) = &mut self.stage.borrow_mut().graphics {
+            for warning in query::apply_query(query, graphics) {
+                web_sys::console::warn_1(&JsValue::from_str(&warning));
+            }
+        }
+    }
+
+    /* Choose how the show is drawn: "webgl" draws many particles a lot
+     * faster, "2d" works everywhere. When WebGL is not available, the show
+     * keeps drawing in 2d and the reason is reported to the console. */
+    pub fn set_renderer(&mut self, name: &str) -> Result<(), JsValue> {
+        let kind = RendererKind::from_name(name)
+            .ok_or_else(|| JsValue::from_str(&format!("There is no renderer called {}.", name)))?;
+
+        if let Some(graphics) = &mut self.stage.borrow_mut().graphics {
+            if let Err(err) = graphics.set_renderer(kind) {
+                web_sys::console::warn_1(&JsValue::from_str(&format!(
+                    "{} Drawing in 2d instead.",
+                    err
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /* The name of the renderer the show is drawn with. */
+    pub fn renderer(&self) -> String {
+        match &self.stage.borrow().graphics {
+            Some(graphics) => graphics.renderer().name().to_string(),
+            None => RendererKind::Canvas2d.name().to_string(),
+        }
+    }
+
+    /* Handle a command from the page, such as the data of a message posted
+     * to the worker the show runs in. */
+    pub fn handle_message(&mut self, message: &JsValue) -> Result<(), JsValue> {
+        match Command::parse(message).map_err(|err| JsValue::from_str(&err))? {
+            Command::Spawn => self.spawn(),
+            Command::Click { x } => self.click(x),
+            Command::Config { settings } => {
+                if let Some(config) = self.config() {
+                    config.merge(&settings)?;
+                }
+            }
+            Command::Resize {
+                width,
+                height,
+                pixel_ratio,
+            } => {
+                if let Some(ratio) = pixel_ratio {
+                    self.set_pixel_ratio(ratio);
+                }
+                self.resize(width, height);
+            }
+            Command::Fields { fields } => self.replace_fields(fields)?,
+            Command::Pointer { x, y } => self.move_pointer(x, y),
+            Command::PointerLeave => self.leave_pointer(),
+            Command::Pause => self.pause(),
+            Command::Resume => self.resume()?,
+        }
+
+        Ok(())
+    }
+
+    /* Run the show by itself: simulate and draw it every animation frame,
+     * spawn fireworks until the canvas is full, and follow the size of the
+     * canvas. Running is paused while the page is hidden. */
+    pub fn run(&mut self) -> Result<(), JsValue> {
+        if self.animation.is_none() {
+            self.animation = Some(Animation::start(self.stage.clone())?);
+        }
+
+        Ok(())
+    }
+
+    /* Pause a running show. */
+    pub fn pause(&self) {
+        if let Some(animation) = &self.animation {
+            animation.pause();
+        }
+    }
+
+    /* Resume a paused show. */
+    pub fn resume(&self) -> Result<(), JsValue> {
+        match &self.animation {
+            Some(animation) => Ok(animation.resume()?),
+            None => Ok(()),
+        }
+    }
+
+    /* Stop running the show by itself. It can still be stepped and drawn by
+     * hand, or be run again. */
+    pub fn stop(&mut self) {
+        self.animation = None;
+    }
+
+    /* Check whether the show is running and not paused. */
+    pub fn is_running(&self) -> bool {
+        self.animation
+            .as_ref()
+            .is_some_and(|animation| !animation.paused())
+    }
+
+    /* Simulate one step. */
+    pub fn step(&mut self) {
+        self.stage.borrow_mut().step();
+    }
+
+    /* Draw the current state of the simulation, and play the sounds made
+     * since the last time it was drawn. */
+    pub fn draw(&mut self) {
+        self.stage.borrow_mut().draw();
+    }
+
+    /* Spawn a new firework. */
+    pub fn spawn(&mut self) {
+        self.stage.borrow_mut().spawn();
+    }
+
+    /* Launch a single rocket from the given x position in CSS pixels, like
+     * where the page was clicked. */
+    pub fn click(&mut self, x: f64) {
+        self.stage.borrow_mut().input(Event::Click { x });
+    }
+
+    /* Change the size of the canvas in CSS pixels. */
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.stage.borrow_mut().resize(width, height);
+    }
+
+    /* Place force fields which push the sparks around, replacing those
+     * there were. The fields are a JSON list of objects like
+     * `{ "kind": "vortex", "x": 400, "y": 300, "strength": 0.2 }`, where the
+     * kind is attractor, repulsor, vortex or flow. Fields can have a radius
+     * in CSS pixels, and can follow the pointer instead with
+     * `"follow_pointer": true`. */
+    pub fn set_fields(&mut self, json: &str) -> Result<(), JsValue> {
+        let fields: Vec<Field> =
+            serde_json::from_str(json).map_err(|err| JsValue::from_str(&err.to_string()))?;
+
+        self.replace_fields(fields)
+    }
+
+    /* Tell the show where the pointer is in CSS pixels, for the fields which
+     * follow it. Without those the pointer is ignored, so recordings don't
+     * fill up with every movement. */
+    pub fn move_pointer(&mut self, x: f64, y: f64) {
+        let mut stage = self.stage.borrow_mut();
+        if stage.follows_pointer() {
+            stage.input(Event::Pointer { x, y });
+        }
+    }
+
+    /* Tell the show the pointer left the canvas. */
+    pub fn leave_pointer(&mut self) {
+        let mut stage = self.stage.borrow_mut();
+        if stage.follows_pointer() {
+            stage.input(Event::PointerLeave);
+        }
+    }
+
+    /* Set the amount of canvas pixels per CSS pixel. Running shows follow
+     * the devicePixelRatio of the window by themselves. */
+    pub fn set_pixel_ratio(&mut self, ratio: f64) {
+        if let Some(graphics) = &mut self.stage.borrow_mut().graphics {
+            graphics.set_pixel_ratio(ratio);
+        }
+    }
+
+    pub fn pixel_ratio(&self) -> f64 {
+        self.stage
+            .borrow()
+            .graphics
+            .as_ref()
+            .map_or(1., Graphics::pixel_ratio)
+    }
+
+    /* Stop the show and clear its canvas. Afterwards the show does nothing. */
+    pub fn destroy(&mut self) {
+        self.stop();
+
+        let mut stage = self.stage.borrow_mut();
+        if let Some(graphics) = stage.graphics.take() {
+            graphics.clear();
+        }
+
+        let _ = stage.audio.set_muted(true);
+    }
+
+    /* Get the configuration of the show, changes to it apply immediately. */
+    pub fn config(&self) -> Option<Config> {
+        self.stage.borrow().graphics.as_ref().map(Graphics::config)
+    }
+
+    /* The maximal amount of fireworks on the canvas. */
+    pub fn max_fireworks(&self) -> u32 {
+        self.stage.borrow().max_fireworks()
+    }
+
+    /* Save the complete state of the show as JSON, to continue it exactly
+     * from this moment with restore. */
+    pub fn save(&self) -> Option<String> {
+        self.stage
+            .borrow()
+            .graphics
+            .as_ref()
+            .map(|graphics| graphics.snapshot().to_json())
+    }
+
+    /* Continue the show from a state saved with save. */
+    pub fn restore(&mut self, json: &str) -> Result<(), JsValue> {
+        let snapshot = Snapshot::from_json(json).map_err(|err| JsValue::from_str(&err))?;
+
+        if let Some(graphics) = &mut self.stage.borrow_mut().graphics {
+            graphics
+                .restore(snapshot)
+                .map_err(|err| JsValue::from_str(&err))?;
+        }
+
+        Ok(())
+    }
+
+    /* The current level of detail, lowered automatically when the device
+     * can't keep up. 0 is the lowest level, and max_detail_level the
+     * highest. */
+    pub fn detail_level(&self) -> usize {
+        self.stage.borrow().governor.level()
+    }
+
+    pub fn max_detail_level(&self) -> usize {
+        self.stage.borrow().governor.max_level()
+    }
+
+    /* Turn the automatic adjustment of the detail on or off. When it is off,
+     * everything is drawn in full detail. */
+    pub fn set_adaptive_quality(&mut self, enabled: bool) {
+        let mut stage = self.stage.borrow_mut();
+        stage.governor.set_enabled(enabled);
+
+        let level = stage.governor.level();
+        stage.input(Event::Detail { level });
+    }
+
+    pub fn is_adaptive_quality(&self) -> bool {
+        self.stage.borrow().governor.enabled()
+    }
+
+    /* Set the frame rate the automatic adjustment of the detail aims for. */
+    pub fn set_target_fps(&mut self, fps: f64) -> Result<(), JsValue> {
+        self.stage
+            .borrow_mut()
+            .governor
+            .set_target_fps(fps)
+            .map_err(|err| JsValue::from_str(&err))
+    }
+
+    /* Mute or unmute the sound of the fireworks. The sound starts out muted,
+     * and can only be unmuted after the user interacted with the page. */
+    pub fn set_muted(&mut self, muted: bool) -> Result<(), JsValue> {
+        self.stage.borrow_mut().audio.set_muted(muted)
+    }
+
+    /* Check whether the sound of the fireworks is muted. */
+    pub fn is_muted(&self) -> bool {
+        self.stage.borrow().audio.muted()
+    }
+
+    /* Launch rockets so they explode on the beats in a JSON timeline. The
+     * timeline is a list of times in seconds, either as plain numbers or as
+     * objects with a `time` field. */
+    pub fn load_beats(&mut self, json: &str) -> Result<(), JsValue> {
+        let timeline = js_sys::JSON::parse(json)?;
+        if !js_sys::Array::is_array(&timeline) {
+            return Err(JsValue::from_str("The beat timeline should be a list."));
+        }
+
+        let mut times = Vec::new();
+        for beat in js_sys::Array::from(&timeline).iter() {
+            let time = match beat.as_f64() {
+                Some(time) => time,
+                None => js_sys::Reflect::get(&beat, &JsValue::from_str("time"))?
+                    .as_f64()
+                    .ok_or_else(|| JsValue::from_str("Every beat should have a time."))?,
+            };
+
+            times.push(time);
+        }
+
+        self.stage.borrow_mut().input(Event::Beats { times });
+
+        Ok(())
+    }
+
+    /* Launch rockets so they explode on the onsets found in decoded mono PCM
+     * samples, such as those of an AudioBuffer channel. */
+    pub fn load_music(&mut self, samples: &[f32], sample_rate: f32) {
+        self.stage.borrow_mut().input(Event::Beats {
+            times: onset::detect_onsets(samples, sample_rate),
+        });
+    }
+
+    /* Tell the beat scheduler how far the music has played, in seconds. */
+    pub fn sync_music(&mut self, time: f64) {
+        self.stage.borrow_mut().input(Event::SyncBeats { time });
+    }
+
+    /* Start recording everything which happens to the show from now on.
+     * Recording again starts over. */
+    pub fn start_recording(&mut self) {
+        let mut stage = self.stage.borrow_mut();
+        let level = stage.governor.level();
+
+        stage.recorder = stage
+            .graphics
+            .as_ref()
+            .map(|graphics| Recorder::start(graphics.snapshot(), level));
+        stage.steps = 0;
+    }
+
+    /* Stop recording, and get the recording as JSON. */
+    pub fn stop_recording(&mut self) -> Option<String> {
+        self.stage
+            .borrow_mut()
+            .recorder
+            .take()
+            .map(|recorder| recorder.finish().to_json())
+    }
+
+    /* Show a recording again exactly as it happened, from the steps taken
+     * from then on. Until the recording ends, the show ignores everything
+     * else which would change it. */
+    pub fn replay(&mut self, json: &str) -> Result<(), JsValue> {
+        let recording = Recording::from_json(json).map_err(|err| JsValue::from_str(&err))?;
+        let (start, replayer) = Replayer::start(recording);
+
+        let mut stage = self.stage.borrow_mut();
+        if let Some(graphics) = &mut stage.graphics {
+            graphics
+                .restore_exactly(start)
+                .map_err(|err| JsValue::from_str(&err))?;
+        }
+
+        stage.recorder = None;
+        stage.replayer = Some(replayer);
+        stage.steps = 0;
+
+        Ok(())
+    }
+
+    /* Check whether the show is replaying a recording. */
+    pub fn is_replaying(&self) -> bool {
+        self.stage.borrow().replayer.is_some()
+    }
+}
+
+/* Find the canvas with the given id, or add one to the page if it doesn't
+ * exist, and make it as large as the window. */
+fn window_canvas(id: &str) -> Result<HtmlCanvasElement, Error> {
+    let window = web_sys::window().ok_or(Error::NoWindow)?;
+    let document = window.document().ok_or(Error::NoDocument)?;
+
+    let canvas = match document.get_element_by_id(id) {
+        Some(element) => element
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(|_| Error::NotACanvas(id.to_string()))?,
+        None => {
+            let canvas = document
+                .create_element("canvas")?
+                .dyn_into::<HtmlCanvasElement>()
+                .map_err(|_| Error::NotACanvas(id.to_string()))?;
+            canvas.set_id(id);
+
+            document
+                .body()
+                .ok_or(Error::NoBody)?
+                .append_child(&canvas)?;
+
+            canvas
+        }
+    };
+
+    let width = window.inner_width()?.as_f64().ok_or(Error::NoWindowSize)?;
+    let height = window.inner_height()?.as_f64().ok_or(Error::NoWindowSize)?;
+
+    /* The size the canvas is shown at is left to the stylesheet, so it
+     * follows the window. The show gives it more pixels on high density
+     * screens. */
+    canvas.set_width(width as u32);
+    canvas.set_height(height as u32);
+
+    Ok(canvas)
+}
+
+/* The amount of screen pixels per CSS pixel. */
+fn device_pixel_ratio() -> f64 {
+    web_sys::window().map_or(1., |window| window.device_pixel_ratio())
+}
+
+/* Log an error to the console, and show it on the page if there is one. */
+fn report_error(err: &Error) {
+    web_sys::console::error_1(&JsValue::from_str(&err.to_string()));
+
+    let document = match web_sys::window().and_then(|window| window.document()) {
+        Some(document) => document,
+        None => return,
+    };
+
+    if let (Some(body), Ok(message)) = (document.body(), document.create_element("p")) {
+        message.set_text_content(Some(&format!("The fireworks could not start: {}", err)));
+        let _ = message.set_attribute(
+            "style",
+            "color: white; font: 20px sans-serif; padding: 1em;",
+        );
+        let _ = body.append_child(&message);
+    }
+}
@@ -0,0 +1,39 @@
+import init, { FireworkShow } from './pkg/new_years.js';
+
+/* Runs a show in a worker. The page first sends a start message with the
+ * canvas from transferControlToOffscreen, after which it can send spawn,
+ * click, config, fields, pointer, resize, pause and resume messages. If the
+ * show fails to start, an error message is sent back. */
+let show = null;
+let ready = null;
+
+self.onmessage = async (event) => {
+    const message = event.data;
+
+    if (message.type === 'start') {
+        ready = (async () => {
+            await init();
+
+            show = FireworkShow.from_offscreen(message.canvas, message.pixel_ratio);
+            show.configure(message.query);
+            show.resize(message.width, message.height);
+            show.run();
+        })().catch((err) => {
+            /* The page runs the show itself instead. */
+            self.postMessage({ type: 'error', message: String(err) });
+        });
+        return;
+    }
+
+    /* Messages sent while the show is starting are handled once it runs,
+     * and ignored if it failed to start. */
+    await ready;
+    if (!show) {
+        return;
+    }
+    try {
+        show.handle_message(message);
+    } catch (err) {
+        console.error(err);
+    }
+};